@@ -1,3 +1,4 @@
 fn main() {
+  #[cfg(feature = "tauri-ui")]
   tauri_build::build()
 }
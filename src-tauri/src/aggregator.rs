@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+use crate::config::Layout;
+use crate::keyway::Key;
+
+/// How long a key may stay down without a press or repeat event before it is
+/// considered stuck, e.g. because its release was lost to a screen lock.
+pub const STUCK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Collects displayed key groups and tracks which keys are held.
+///
+/// Keys are identified by any copyable id, e.g. the device and key code.
+#[derive(Debug)]
+pub struct Aggregator<K> {
+    groups: Vec<Vec<Key>>,
+    held: HashMap<K, Instant>,
+    last_input: Instant,
+    stuck_timeout: Duration,
+}
+
+impl<K: Copy + Eq + Hash> Aggregator<K> {
+    pub fn new(stuck_timeout: Duration) -> Self {
+        Aggregator {
+            groups: Vec::new(),
+            held: HashMap::new(),
+            last_input: Instant::now(),
+            stuck_timeout,
+        }
+    }
+    /// Records a press or auto-repeat of `key`.
+    pub fn press(&mut self, key: K, now: Instant) {
+        self.held.insert(key, now);
+        self.last_input = now;
+    }
+    pub fn release(&mut self, key: K, now: Instant) {
+        self.held.remove(&key);
+        self.last_input = now;
+    }
+    pub fn push(&mut self, group: Vec<Key>) {
+        if !group.is_empty() {
+            self.groups.push(group);
+        }
+    }
+    /// Clears the groups once no input arrived for `timeout` and returns the
+    /// keys that have been held without any event for the stuck timeout. The
+    /// returned keys are forgotten; the backend should release them too.
+    pub fn expire(&mut self, now: Instant, timeout: Duration) -> Vec<K> {
+        if !self.groups.is_empty() && now.duration_since(self.last_input) > timeout {
+            self.groups.clear();
+        }
+        let stuck = self
+            .held
+            .iter()
+            .filter(|(_, seen)| now.duration_since(**seen) > self.stuck_timeout)
+            .map(|(key, _)| *key)
+            .collect::<Vec<_>>();
+        for key in stuck.iter() {
+            self.held.remove(key);
+        }
+        stuck
+    }
+    /// The groups, oldest first.
+    pub fn groups(&self) -> &[Vec<Key>] {
+        &self.groups
+    }
+    /// The groups in display order for `layout`, i.e. left to right or top
+    /// to bottom.
+    pub fn arranged(&self, layout: Layout) -> Vec<Vec<Key>> {
+        match layout {
+            Layout::NewestOnTop => self.groups.iter().rev().cloned().collect(),
+            Layout::Horizontal | Layout::NewestOnBottom => self.groups.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testutil::group;
+
+    #[test]
+    fn test_groups_clear_after_timeout() {
+        let start = Instant::now();
+        let timeout = Duration::from_millis(500);
+        let mut aggregator = Aggregator::new(STUCK_TIMEOUT);
+        aggregator.press(30, start);
+        aggregator.push(group(&["a"]));
+        aggregator.push(Vec::new());
+        aggregator.release(30, start + Duration::from_millis(100));
+        aggregator.expire(start + Duration::from_millis(500), timeout);
+        assert_eq!(aggregator.groups(), &[group(&["a"])]);
+        aggregator.expire(start + Duration::from_millis(700), timeout);
+        assert!(aggregator.groups().is_empty());
+    }
+
+    #[test]
+    fn test_stuck_keys_expire() {
+        let start = Instant::now();
+        let timeout = Duration::from_millis(500);
+        let mut aggregator = Aggregator::new(Duration::from_secs(2));
+        aggregator.press(29, start);
+        aggregator.press(30, start);
+        aggregator.release(30, start);
+        assert!(aggregator
+            .expire(start + Duration::from_secs(1), timeout)
+            .is_empty());
+        // repeats keep a held key alive
+        aggregator.press(42, start + Duration::from_secs(1));
+        aggregator.press(42, start + Duration::from_secs(2));
+        assert_eq!(
+            aggregator.expire(start + Duration::from_secs(3), timeout),
+            vec![29]
+        );
+        assert!(aggregator
+            .expire(start + Duration::from_secs(3), timeout)
+            .is_empty());
+        assert_eq!(
+            aggregator.expire(start + Duration::from_secs(5), timeout),
+            vec![42]
+        );
+    }
+
+    #[test]
+    fn test_arranged() {
+        let mut aggregator = Aggregator::<u16>::new(STUCK_TIMEOUT);
+        aggregator.push(group(&["a"]));
+        aggregator.push(group(&["Ctrl", "b"]));
+        assert_eq!(
+            aggregator.arranged(Layout::Horizontal),
+            [group(&["a"]), group(&["Ctrl", "b"])]
+        );
+        assert_eq!(
+            aggregator.arranged(Layout::NewestOnBottom),
+            aggregator.groups()
+        );
+        assert_eq!(
+            aggregator.arranged(Layout::NewestOnTop),
+            [group(&["Ctrl", "b"]), group(&["a"])]
+        );
+    }
+}
@@ -0,0 +1,86 @@
+//! Commands invoked by the windows. Changes are validated by the
+//! [`AppState`], and a chosen monitor against the connected ones, before they
+//! are applied. The resulting config is returned so the window shows what is
+//! in effect.
+use keyway::config::{Config, ConfigPatch, FontFamily};
+use keyway::error::{Error, Result};
+use keyway::state::AppState;
+use keyway::theme::{Theme, Themes};
+use log::debug;
+use serde::Serialize;
+use std::sync::{Arc, RwLock};
+use tauri::{State, Window};
+
+#[derive(Debug, Serialize)]
+pub struct MonitorInfo {
+    pub name: Option<String>,
+    pub width: u32,
+    pub height: u32,
+    pub scale_factor: f64,
+}
+
+#[tauri::command]
+pub fn get_config(state: State<AppState>) -> Config {
+    state.config()
+}
+
+#[tauri::command]
+pub fn set_config(patch: ConfigPatch, state: State<AppState>, window: Window) -> Result<Config> {
+    debug!("set_config: {:?}", patch);
+    if let Some(name) = patch.position.as_ref().and_then(|p| p.monitor.as_ref()) {
+        check_monitor(&window, name, &state.config())?;
+    }
+    state.set_config(patch)
+}
+
+/// Rejects a monitor that is not connected, unless it is the one already
+/// chosen, which may only be unplugged for now.
+fn check_monitor(window: &Window, name: &str, config: &Config) -> Result<()> {
+    if config.position.monitor.as_deref() == Some(name) {
+        return Ok(());
+    }
+    let monitors = window.available_monitors()?;
+    if monitors.iter().any(|m| m.name().is_some_and(|n| n == name)) {
+        return Ok(());
+    }
+    Err(Error::Config {
+        field: "monitor",
+        message: format!("{name:?} is not connected"),
+    })
+}
+
+/// The font families the typography may use, for the font picker.
+#[tauri::command]
+pub fn list_fonts(state: State<AppState>) -> Vec<FontFamily> {
+    state.fonts().list()
+}
+
+/// The names of the keycap themes, for the theme picker.
+#[tauri::command]
+pub fn list_themes(themes: State<Arc<RwLock<Themes>>>) -> Vec<String> {
+    themes.read().unwrap().names()
+}
+
+/// The keycap theme selected in the config.
+#[tauri::command]
+pub fn get_theme(state: State<AppState>, themes: State<Arc<RwLock<Themes>>>) -> Theme {
+    themes
+        .read()
+        .unwrap()
+        .get(&state.config().windowappearance.theme)
+}
+
+/// The monitors the key window can be placed on.
+#[tauri::command]
+pub fn list_monitors(window: Window) -> Result<Vec<MonitorInfo>> {
+    let monitors = window.available_monitors()?;
+    Ok(monitors
+        .into_iter()
+        .map(|m| MonitorInfo {
+            name: m.name().cloned(),
+            width: m.size().width,
+            height: m.size().height,
+            scale_factor: m.scale_factor(),
+        })
+        .collect())
+}
@@ -0,0 +1,653 @@
+//! Settings sent by the config window, mirrored in `src/parameter.tsx`, and
+//! their validation.
+use log::{debug, warn};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+use crate::error::{Error, Result};
+use crate::geometry::Anchor;
+use crate::theme;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BehaviorParameter {
+    pub timeout: u32,
+    pub mousevisible: bool,
+    pub modvisible: bool,
+    #[serde(default)]
+    pub modifierside: bool,
+    /// Let clicks pass through the key window to what is below it.
+    #[serde(default)]
+    pub clickthrough: bool,
+    #[serde(default)]
+    pub display: DisplayStyle,
+}
+
+/// When the key window is visible.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DisplayStyle {
+    #[default]
+    Always,
+    /// Only while keys are shown, it fades out once they time out.
+    WhileInputting,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TypographyParameter {
+    pub fontsize: u32,
+    pub fontfamily: String,
+    pub textcolor: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WindowAppearanceParameter {
+    pub backgroundcolor: String,
+    pub transparantetoggle: bool,
+    pub backgroundopacity: f32,
+    #[serde(default)]
+    pub layout: Layout,
+    /// Name of the keycap theme.
+    #[serde(default = "default_theme")]
+    pub theme: String,
+}
+
+fn default_theme() -> String {
+    theme::DEFAULT_THEME.to_string()
+}
+
+/// How the key groups are laid out in the key window.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Layout {
+    /// In a row, oldest on the left.
+    #[default]
+    Horizontal,
+    /// Stacked, the newest group on top.
+    NewestOnTop,
+    /// Stacked, the newest group at the bottom.
+    NewestOnBottom,
+}
+
+impl Layout {
+    pub fn is_vertical(self) -> bool {
+        self != Layout::Horizontal
+    }
+}
+
+/// Which monitor the key window moves to on its own.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Follow {
+    /// Stay on the chosen monitor.
+    #[default]
+    Off,
+    /// The monitor with the mouse pointer.
+    Pointer,
+    /// The monitor with the focused window.
+    Focus,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PositionParameter {
+    pub anchor: Anchor,
+    /// Distance from the anchored edges in logical pixels.
+    pub margin: u32,
+    /// Name of the monitor to show the key window on, None for the one it
+    /// is on. While the named monitor is not connected the window stays on
+    /// the one it is on.
+    #[serde(default)]
+    pub monitor: Option<String>,
+    #[serde(default)]
+    pub follow: Follow,
+}
+
+pub const CONFIG_FILE: &str = "config.json";
+
+/// All settings, the defaults match the initial values of the config window.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Config {
+    pub behavior: BehaviorParameter,
+    pub typography: TypographyParameter,
+    pub windowappearance: WindowAppearanceParameter,
+    #[serde(default)]
+    pub position: PositionParameter,
+    /// Kept by the app rather than set from the config window.
+    #[serde(default)]
+    pub placements: Placements,
+}
+
+/// Where the free key window was left, per monitor setup.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Placements {
+    /// Top left corners in physical pixels by
+    /// [`setup_key`](crate::geometry::setup_key).
+    pub positions: BTreeMap<String, (i32, i32)>,
+    /// The setup the window was last moved in.
+    pub last: Option<String>,
+}
+
+impl Placements {
+    /// Records the position in `setup`, returning whether anything changed.
+    pub fn set(&mut self, setup: &str, position: (i32, i32)) -> bool {
+        let changed =
+            self.positions.get(setup) != Some(&position) || self.last.as_deref() != Some(setup);
+        self.positions.insert(setup.to_string(), position);
+        self.last = Some(setup.to_string());
+        changed
+    }
+    /// The position in `setup`, or else the last one in another setup.
+    pub fn get(&self, setup: &str) -> Option<(i32, i32)> {
+        self.positions
+            .get(setup)
+            .or_else(|| self.positions.get(self.last.as_ref()?))
+            .copied()
+    }
+}
+
+/// A change to some sections of the [`Config`], the missing ones are kept.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ConfigPatch {
+    pub behavior: Option<BehaviorParameter>,
+    pub typography: Option<TypographyParameter>,
+    pub windowappearance: Option<WindowAppearanceParameter>,
+    pub position: Option<PositionParameter>,
+}
+
+impl Default for BehaviorParameter {
+    fn default() -> Self {
+        BehaviorParameter {
+            timeout: 500,
+            mousevisible: false,
+            modvisible: false,
+            modifierside: false,
+            clickthrough: false,
+            display: DisplayStyle::Always,
+        }
+    }
+}
+
+impl Default for TypographyParameter {
+    fn default() -> Self {
+        TypographyParameter {
+            fontsize: 12,
+            fontfamily: "SansSerif".to_string(),
+            textcolor: "#e0e0e0".to_string(),
+        }
+    }
+}
+
+impl Default for WindowAppearanceParameter {
+    fn default() -> Self {
+        WindowAppearanceParameter {
+            backgroundcolor: "#e0e0e0".to_string(),
+            transparantetoggle: true,
+            backgroundopacity: 50.0,
+            layout: Layout::Horizontal,
+            theme: default_theme(),
+        }
+    }
+}
+
+impl Default for PositionParameter {
+    fn default() -> Self {
+        PositionParameter {
+            anchor: Anchor::Free,
+            margin: 16,
+            monitor: None,
+            follow: Follow::Off,
+        }
+    }
+}
+
+pub const TIMEOUT_RANGE: RangeInclusive<u32> = 50..=2000;
+pub const FONTSIZE_RANGE: RangeInclusive<u32> = 8..=32;
+pub const OPACITY_RANGE: RangeInclusive<f32> = 0.0..=100.0;
+pub const MARGIN_RANGE: RangeInclusive<u32> = 0..=200;
+/// CSS generic families, accepted whether or not a font is installed.
+const GENERIC_FAMILIES: &[&str] = &[
+    "SansSerif",
+    "sans-serif",
+    "Serif",
+    "serif",
+    "Monospace",
+    "monospace",
+    "cursive",
+    "fantasy",
+    "system-ui",
+];
+
+fn invalid(field: &'static str, message: String) -> Error {
+    Error::Config { field, message }
+}
+
+/// A color parsed from `#rgb`, `#rgba`, `#rrggbb` or `#rrggbbaa`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rgba {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Rgba {
+    pub fn parse(s: &str) -> Option<Self> {
+        let hex = s.trim().strip_prefix('#')?;
+        if !hex.is_ascii() {
+            return None;
+        }
+        let digits = match hex.len() {
+            3 | 4 => hex
+                .chars()
+                .map(|c| c.to_digit(16).map(|v| v as u8 * 0x11))
+                .collect::<Option<Vec<_>>>()?,
+            6 | 8 => (0..hex.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+                .collect::<Option<Vec<_>>>()?,
+            _ => return None,
+        };
+        Some(Rgba {
+            r: digits[0],
+            g: digits[1],
+            b: digits[2],
+            a: digits.get(3).copied().unwrap_or(0xff),
+        })
+    }
+}
+
+impl fmt::Display for Rgba {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)?;
+        if self.a != 0xff {
+            write!(f, "{:02x}", self.a)?;
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn parse_color(field: &'static str, s: &str) -> Result<String> {
+    Rgba::parse(s)
+        .map(|c| c.to_string())
+        .ok_or_else(|| invalid(field, format!("{s:?} is not a color")))
+}
+
+/// The generic families offered in the font picker, before the installed ones.
+const PICKER_GENERIC_FAMILIES: &[(&str, bool)] =
+    &[("SansSerif", false), ("Serif", false), ("Monospace", true)];
+
+/// A font family as offered in the font picker.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FontFamily {
+    pub name: String,
+    /// Whether the family has a fixed pitch face.
+    pub monospace: bool,
+    /// Whether this is a CSS generic family rather than an installed one.
+    pub generic: bool,
+}
+
+/// The font families installed on this machine, by name, with whether they
+/// are monospaced.
+#[derive(Debug, Clone, Default)]
+pub struct FontFamilies(BTreeMap<String, bool>);
+
+impl FontFamilies {
+    pub fn contains(&self, family: &str) -> bool {
+        GENERIC_FAMILIES.contains(&family) || self.0.contains_key(family)
+    }
+    /// The generic families followed by the installed ones, sorted by name.
+    pub fn list(&self) -> Vec<FontFamily> {
+        let generic = PICKER_GENERIC_FAMILIES
+            .iter()
+            .map(|&(name, monospace)| FontFamily {
+                name: name.to_string(),
+                monospace,
+                generic: true,
+            });
+        let installed = self.0.iter().map(|(name, &monospace)| FontFamily {
+            name: name.clone(),
+            monospace,
+            generic: false,
+        });
+        generic.chain(installed).collect()
+    }
+}
+
+/// Collects the families of font faces, a family is monospaced if any of its
+/// faces is.
+impl FromIterator<(String, bool)> for FontFamilies {
+    fn from_iter<I: IntoIterator<Item = (String, bool)>>(iter: I) -> Self {
+        let mut families = BTreeMap::new();
+        for (name, monospace) in iter {
+            *families.entry(name).or_insert(false) |= monospace;
+        }
+        FontFamilies(families)
+    }
+}
+
+impl BehaviorParameter {
+    /// Clamps the timeout into [`TIMEOUT_RANGE`].
+    pub fn validate(mut self) -> Result<Self> {
+        self.timeout = self
+            .timeout
+            .clamp(*TIMEOUT_RANGE.start(), *TIMEOUT_RANGE.end());
+        Ok(self)
+    }
+}
+
+impl TypographyParameter {
+    /// Clamps the font size and normalizes the color, unknown families and
+    /// colors are rejected.
+    pub fn validate(mut self, families: &FontFamilies) -> Result<Self> {
+        self.fontsize = self
+            .fontsize
+            .clamp(*FONTSIZE_RANGE.start(), *FONTSIZE_RANGE.end());
+        if !families.contains(&self.fontfamily) {
+            return Err(invalid(
+                "fontfamily",
+                format!("{:?} is not installed", self.fontfamily),
+            ));
+        }
+        self.textcolor = parse_color("textcolor", &self.textcolor)?;
+        Ok(self)
+    }
+}
+
+impl WindowAppearanceParameter {
+    /// Clamps the opacity and normalizes the color.
+    pub fn validate(mut self) -> Result<Self> {
+        if self.backgroundopacity.is_nan() {
+            return Err(invalid("backgroundopacity", "not a number".to_string()));
+        }
+        self.backgroundopacity = self
+            .backgroundopacity
+            .clamp(*OPACITY_RANGE.start(), *OPACITY_RANGE.end());
+        self.backgroundcolor = parse_color("backgroundcolor", &self.backgroundcolor)?;
+        Ok(self)
+    }
+}
+
+impl PositionParameter {
+    /// Clamps the margin into [`MARGIN_RANGE`].
+    pub fn validate(mut self) -> Result<Self> {
+        self.margin = self
+            .margin
+            .clamp(*MARGIN_RANGE.start(), *MARGIN_RANGE.end());
+        Ok(self)
+    }
+}
+
+impl ConfigPatch {
+    /// Validates every section of the patch, nothing is applied if one of
+    /// them is rejected.
+    pub fn validate(self, families: &FontFamilies) -> Result<Self> {
+        Ok(ConfigPatch {
+            behavior: self.behavior.map(BehaviorParameter::validate).transpose()?,
+            typography: self.typography.map(|t| t.validate(families)).transpose()?,
+            windowappearance: self
+                .windowappearance
+                .map(WindowAppearanceParameter::validate)
+                .transpose()?,
+            position: self.position.map(PositionParameter::validate).transpose()?,
+        })
+    }
+}
+
+/// Reads section `name` of a saved config, the default if it is missing or
+/// invalid.
+fn load_section<T: DeserializeOwned + Default>(
+    saved: &Map<String, Value>,
+    name: &str,
+    path: &Path,
+    validate: impl FnOnce(T) -> Result<T>,
+) -> T {
+    let Some(value) = saved.get(name) else {
+        return T::default();
+    };
+    T::deserialize(value)
+        .map_err(Error::from)
+        .and_then(validate)
+        .unwrap_or_else(|e| {
+            warn!("Ignoring saved {name} in {:?}: {e}", path);
+            T::default()
+        })
+}
+
+impl Config {
+    /// Reads the saved config. A missing or malformed file gives the
+    /// defaults, an invalid section gives its defaults and keeps the others.
+    pub fn load(path: &Path, families: &FontFamilies) -> Self {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) => {
+                debug!("No saved config {:?}: {e}", path);
+                return Config::default();
+            }
+        };
+        let saved = match serde_json::from_str::<Map<String, Value>>(&text) {
+            Ok(saved) => saved,
+            Err(e) => {
+                warn!("Ignoring saved config {:?}: {e}", path);
+                return Config::default();
+            }
+        };
+        debug!("Loaded config {:?}", path);
+        Config {
+            behavior: load_section(&saved, "behavior", path, BehaviorParameter::validate),
+            typography: load_section(&saved, "typography", path, |t: TypographyParameter| {
+                t.validate(families)
+            }),
+            windowappearance: load_section(
+                &saved,
+                "windowappearance",
+                path,
+                WindowAppearanceParameter::validate,
+            ),
+            position: load_section(&saved, "position", path, PositionParameter::validate),
+            placements: load_section(&saved, "placements", path, Ok),
+        }
+    }
+    /// Writes the config through a temporary file, so a crash never leaves
+    /// a truncated one behind.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+    /// Applies a validated patch, returning whether anything changed.
+    pub fn apply(&mut self, patch: ConfigPatch) -> bool {
+        let before = self.clone();
+        if let Some(behavior) = patch.behavior {
+            self.behavior = behavior;
+        }
+        if let Some(typography) = patch.typography {
+            self.typography = typography;
+        }
+        if let Some(windowappearance) = patch.windowappearance {
+            self.windowappearance = windowappearance;
+        }
+        if let Some(position) = patch.position {
+            self.position = position;
+        }
+        *self != before
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_rgba() {
+        let c = |r, g, b, a| Some(Rgba { r, g, b, a });
+        assert_eq!(Rgba::parse("#e0e0e0"), c(0xe0, 0xe0, 0xe0, 0xff));
+        assert_eq!(Rgba::parse("#E0E0E080"), c(0xe0, 0xe0, 0xe0, 0x80));
+        assert_eq!(Rgba::parse("#f08"), c(0xff, 0x00, 0x88, 0xff));
+        assert_eq!(Rgba::parse("#f080"), c(0xff, 0x00, 0x88, 0x00));
+        for bad in ["", "e0e0e0", "#e0e0e", "#gggggg", "red", "#éé"] {
+            assert_eq!(Rgba::parse(bad), None, "{bad}");
+        }
+        assert_eq!(Rgba::parse("#F08").unwrap().to_string(), "#ff0088");
+        assert_eq!(Rgba::parse("#f080").unwrap().to_string(), "#ff008800");
+    }
+
+    #[test]
+    fn test_save_load() {
+        let families = FontFamilies::default();
+        let dir = std::env::temp_dir().join(format!("keyway-config-{}", std::process::id()));
+        let path = dir.join(CONFIG_FILE);
+        assert_eq!(Config::load(&path, &families), Config::default());
+
+        let mut config = Config::default();
+        config.position.monitor = Some("HDMI-1".to_string());
+        config.position.anchor = Anchor::BottomRight;
+        config.behavior.display = DisplayStyle::WhileInputting;
+        config.placements.set("1920x1080+0+0", (100, -20));
+        config.save(&path).unwrap();
+        assert_eq!(Config::load(&path, &families), config);
+
+        // missing sections are filled with defaults
+        let saved = r##"{
+            "behavior": {"timeout": 800, "mousevisible": false, "modvisible": false},
+            "typography": {"fontsize": 12, "fontfamily": "Monospace", "textcolor": "#fff"},
+            "windowappearance": {"backgroundcolor": "#000", "transparantetoggle": false, "backgroundopacity": 20}
+        }"##;
+        fs::write(&path, saved).unwrap();
+        let loaded = Config::load(&path, &families);
+        assert_eq!(loaded.behavior.timeout, 800);
+        assert_eq!(loaded.behavior.display, DisplayStyle::Always);
+        assert_eq!(loaded.typography.textcolor, "#ffffff");
+        assert_eq!(loaded.position, PositionParameter::default());
+        assert_eq!(loaded.placements, Placements::default());
+
+        // an invalid section keeps the others
+        let saved = r##"{
+            "behavior": {"timeout": 800, "mousevisible": false, "modvisible": false},
+            "typography": {"fontsize": 20, "fontfamily": "Monospace", "textcolor": "white"},
+            "windowappearance": {"backgroundcolor": "#000", "transparantetoggle": false, "backgroundopacity": "half"},
+            "position": {"anchor": "bottom-right", "margin": 8},
+            "placements": {"positions": {"1920x1080+0+0": [100, -20]}, "last": "1920x1080+0+0"}
+        }"##;
+        fs::write(&path, saved).unwrap();
+        let loaded = Config::load(&path, &families);
+        assert_eq!(loaded.behavior.timeout, 800);
+        assert_eq!(loaded.typography, TypographyParameter::default());
+        assert_eq!(
+            loaded.windowappearance,
+            WindowAppearanceParameter::default()
+        );
+        assert_eq!(loaded.position.anchor, Anchor::BottomRight);
+        assert_eq!(loaded.placements, config.placements);
+
+        fs::write(&path, "{").unwrap();
+        assert_eq!(Config::load(&path, &families), Config::default());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_validate() {
+        let families = [("Noto Sans".to_string(), false)].into_iter().collect();
+        let typography = |fontsize, fontfamily: &str, textcolor: &str| TypographyParameter {
+            fontsize,
+            fontfamily: fontfamily.to_string(),
+            textcolor: textcolor.to_string(),
+        };
+        assert_eq!(
+            typography(100, "Noto Sans", "#FFF")
+                .validate(&families)
+                .unwrap(),
+            typography(32, "Noto Sans", "#ffffff")
+        );
+        assert!(typography(12, "Monospace", "#fff")
+            .validate(&families)
+            .is_ok());
+        assert!(matches!(
+            typography(12, "Comic Sans", "#fff").validate(&families),
+            Err(Error::Config {
+                field: "fontfamily",
+                ..
+            })
+        ));
+        assert!(matches!(
+            typography(12, "Noto Sans", "white").validate(&families),
+            Err(Error::Config {
+                field: "textcolor",
+                ..
+            })
+        ));
+
+        let appearance = |backgroundopacity| WindowAppearanceParameter {
+            backgroundcolor: "#000".to_string(),
+            transparantetoggle: true,
+            backgroundopacity,
+            layout: Layout::NewestOnTop,
+            theme: "mechanical".to_string(),
+        };
+        assert_eq!(
+            appearance(150.0).validate().unwrap().backgroundopacity,
+            100.0
+        );
+        assert!(appearance(f32::NAN).validate().is_err());
+
+        let behavior = BehaviorParameter {
+            timeout: 0,
+            mousevisible: false,
+            modvisible: false,
+            modifierside: false,
+            clickthrough: true,
+            display: DisplayStyle::WhileInputting,
+        };
+        assert_eq!(behavior.validate().unwrap().timeout, 50);
+    }
+
+    #[test]
+    fn test_font_families() {
+        let families: FontFamilies = [
+            ("Noto Sans Mono".to_string(), true),
+            ("DejaVu Sans".to_string(), false),
+            // another face of the same family
+            ("Noto Sans Mono".to_string(), false),
+        ]
+        .into_iter()
+        .collect();
+        let listed = families
+            .list()
+            .into_iter()
+            .map(|f| (f.name, f.monospace, f.generic))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            listed,
+            [
+                ("SansSerif".to_string(), false, true),
+                ("Serif".to_string(), false, true),
+                ("Monospace".to_string(), true, true),
+                ("DejaVu Sans".to_string(), false, false),
+                ("Noto Sans Mono".to_string(), true, false),
+            ]
+        );
+        assert!(families.contains("DejaVu Sans"));
+        assert!(families.contains("sans-serif"));
+        assert!(!families.contains("Noto Sans"));
+    }
+
+    #[test]
+    fn test_placements() {
+        let mut placements = Placements::default();
+        assert_eq!(placements.get("1920x1080+0+0"), None);
+        assert!(placements.set("1920x1080+0+0", (10, 20)));
+        assert!(!placements.set("1920x1080+0+0", (10, 20)));
+        assert!(placements.set("1920x1080+0+0,1280x720+1920+0", (2000, 20)));
+        assert_eq!(placements.get("1920x1080+0+0"), Some((10, 20)));
+        // an unknown setup gets the last position, to be clamped on screen
+        assert_eq!(placements.get("1280x720+0+0"), Some((2000, 20)));
+    }
+}
@@ -0,0 +1,123 @@
+//! Where the user is working: the mouse pointer and the focused window, in
+//! physical screen coordinates.
+//!
+//! On Linux this asks the X server, so it only sees X11 (and XWayland)
+//! windows; under plain Wayland both queries return None.
+use crate::geometry::Rect;
+
+#[cfg(target_os = "windows")]
+pub use self::win32::Desktop;
+#[cfg(target_os = "linux")]
+pub use self::x11::Desktop;
+
+#[cfg(target_os = "linux")]
+mod x11 {
+    use super::Rect;
+    use std::mem::MaybeUninit;
+    use std::ptr;
+    use x11_dl::xlib::{Display, Xlib};
+
+    pub struct Desktop {
+        xlib: Xlib,
+        display: *mut Display,
+    }
+
+    impl Desktop {
+        pub fn open() -> Option<Self> {
+            let xlib = Xlib::open().ok()?;
+            let display = unsafe { (xlib.XOpenDisplay)(ptr::null()) };
+            if display.is_null() {
+                return None;
+            }
+            Some(Desktop { xlib, display })
+        }
+        pub fn pointer(&self) -> Option<(i32, i32)> {
+            let (mut root, mut child) = (0, 0);
+            let (mut x, mut y, mut win_x, mut win_y, mut mask) = (0, 0, 0, 0, 0);
+            let found = unsafe {
+                (self.xlib.XQueryPointer)(
+                    self.display,
+                    (self.xlib.XDefaultRootWindow)(self.display),
+                    &mut root,
+                    &mut child,
+                    &mut x,
+                    &mut y,
+                    &mut win_x,
+                    &mut win_y,
+                    &mut mask,
+                )
+            };
+            (found != 0).then_some((x, y))
+        }
+        pub fn focused_window(&self) -> Option<Rect> {
+            unsafe {
+                let (mut focus, mut revert) = (0, 0);
+                (self.xlib.XGetInputFocus)(self.display, &mut focus, &mut revert);
+                // 0 is None and 1 PointerRoot
+                if focus <= 1 {
+                    return None;
+                }
+                let mut attrs = MaybeUninit::uninit();
+                if (self.xlib.XGetWindowAttributes)(self.display, focus, attrs.as_mut_ptr()) == 0 {
+                    return None;
+                }
+                let attrs = attrs.assume_init();
+                let (mut x, mut y, mut child) = (0, 0, 0);
+                (self.xlib.XTranslateCoordinates)(
+                    self.display,
+                    focus,
+                    attrs.root,
+                    0,
+                    0,
+                    &mut x,
+                    &mut y,
+                    &mut child,
+                );
+                Some(Rect {
+                    x,
+                    y,
+                    width: attrs.width as u32,
+                    height: attrs.height as u32,
+                })
+            }
+        }
+    }
+
+    impl Drop for Desktop {
+        fn drop(&mut self) {
+            unsafe { (self.xlib.XCloseDisplay)(self.display) };
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod win32 {
+    use super::Rect;
+    use windows::Win32::Foundation::{POINT, RECT};
+    use windows::Win32::UI::WindowsAndMessaging::{
+        GetCursorPos, GetForegroundWindow, GetWindowRect,
+    };
+
+    pub struct Desktop;
+
+    impl Desktop {
+        pub fn open() -> Option<Self> {
+            Some(Desktop)
+        }
+        pub fn pointer(&self) -> Option<(i32, i32)> {
+            let mut point = POINT::default();
+            unsafe { GetCursorPos(&mut point) }.ok()?;
+            Some((point.x, point.y))
+        }
+        pub fn focused_window(&self) -> Option<Rect> {
+            let mut rect = RECT::default();
+            unsafe { GetWindowRect(GetForegroundWindow(), &mut rect) }.ok()?;
+            Some(Rect {
+                x: rect.left,
+                y: rect.top,
+                width: (rect.right - rect.left).max(0) as u32,
+                height: (rect.bottom - rect.top).max(0) as u32,
+            })
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use serde::{Serialize, Serializer};
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    /// A single input device failed, e.g. because it was unplugged.
+    Device {
+        path: PathBuf,
+        source: io::Error,
+    },
+    /// No keyboard could be opened.
+    NoDevices,
+    /// A keymap could not be compiled.
+    Keymap(String),
+    /// A recording made on another platform, whose key codes mean other keys.
+    Platform(String),
+    Json(serde_json::Error),
+    /// Sending to the UI failed.
+    Emit(String),
+    /// A setting from the config window was rejected.
+    Config {
+        field: &'static str,
+        message: String,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{e}"),
+            Error::Device { path, source } => write!(f, "{}: {source}", path.display()),
+            Error::NoDevices => write!(f, "no keyboard found"),
+            Error::Keymap(name) => write!(f, "failed to compile keymap {name}"),
+            Error::Platform(platform) => write!(
+                f,
+                "recorded on {platform}, cannot replay on {}",
+                std::env::consts::OS
+            ),
+            Error::Json(e) => write!(f, "invalid JSON: {e}"),
+            Error::Emit(e) => write!(f, "failed to emit: {e}"),
+            Error::Config { field, message } => write!(f, "invalid {field}: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) | Error::Device { source: e, .. } => Some(e),
+            Error::Json(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Errors are sent to the UI as their message, e.g. as the error of a command.
+impl Serialize for Error {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Json(e)
+    }
+}
+
+#[cfg(feature = "tauri-ui")]
+impl From<tauri::Error> for Error {
+    fn from(e: tauri::Error) -> Self {
+        Error::Emit(e.to_string())
+    }
+}
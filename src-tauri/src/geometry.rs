@@ -0,0 +1,218 @@
+//! Placement of the key window on a monitor, in physical pixels.
+use serde::{Deserialize, Serialize};
+
+/// Where the key window sits on its monitor. With `Free` it stays wherever
+/// it was dragged to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Anchor {
+    #[default]
+    Free,
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect {
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x
+            && y >= self.y
+            && x < self.x + self.width as i32
+            && y < self.y + self.height as i32
+    }
+    pub fn center(&self) -> (i32, i32) {
+        (
+            self.x + self.width as i32 / 2,
+            self.y + self.height as i32 / 2,
+        )
+    }
+}
+
+/// The index of the monitor containing the point, or of the nearest one
+/// when the point is off screen.
+pub fn monitor_at(monitors: &[Rect], (x, y): (i32, i32)) -> Option<usize> {
+    monitors.iter().position(|m| m.contains(x, y)).or_else(|| {
+        (0..monitors.len()).min_by_key(|&i| {
+            let (cx, cy) = monitors[i].center();
+            (cx - x).unsigned_abs() as u64 + (cy - y).unsigned_abs() as u64
+        })
+    })
+}
+
+/// Identifies a monitor setup by the areas of its monitors, in any order.
+pub fn setup_key(monitors: &[Rect]) -> String {
+    let mut monitors = monitors.to_vec();
+    monitors.sort_by_key(|m| (m.x, m.y, m.width, m.height));
+    monitors
+        .iter()
+        .map(|m| format!("{}x{}+{}+{}", m.width, m.height, m.x, m.y))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Moves a `width` x `height` window at `(x, y)` onto the monitor nearest to
+/// its center, as far as needed for it to be fully visible.
+pub fn clamp_onto(monitors: &[Rect], (x, y): (i32, i32), width: u32, height: u32) -> (i32, i32) {
+    let center = (x + width as i32 / 2, y + height as i32 / 2);
+    let Some(i) = monitor_at(monitors, center) else {
+        return (x, y);
+    };
+    let m = monitors[i];
+    let clamp = |v: i32, start: i32, length: u32, size: u32| {
+        v.min(start + length as i32 - size as i32).max(start)
+    };
+    (
+        clamp(x, m.x, m.width, width),
+        clamp(y, m.y, m.height, height),
+    )
+}
+
+/// Where along an axis the window is placed: start, middle or end.
+#[derive(Clone, Copy)]
+enum Align {
+    Start,
+    Middle,
+    End,
+}
+
+fn align(align: Align, start: i32, length: u32, size: u32, margin: i32) -> i32 {
+    match align {
+        Align::Start => start + margin,
+        Align::Middle => start + (length as i32 - size as i32) / 2,
+        Align::End => start + length as i32 - size as i32 - margin,
+    }
+}
+
+impl Anchor {
+    fn aligns(self) -> Option<(Align, Align)> {
+        use Align::*;
+        match self {
+            Anchor::Free => None,
+            Anchor::TopLeft => Some((Start, Start)),
+            Anchor::Top => Some((Middle, Start)),
+            Anchor::TopRight => Some((End, Start)),
+            Anchor::Left => Some((Start, Middle)),
+            Anchor::Center => Some((Middle, Middle)),
+            Anchor::Right => Some((End, Middle)),
+            Anchor::BottomLeft => Some((Start, End)),
+            Anchor::Bottom => Some((Middle, End)),
+            Anchor::BottomRight => Some((End, End)),
+        }
+    }
+    /// The top left corner of a `width` x `height` window anchored on
+    /// `monitor`, `margin` away from the edges it is anchored to. Since the
+    /// position follows from the size, a window anchored right grows to the
+    /// left. Returns None for `Free`.
+    pub fn position(
+        self,
+        monitor: Rect,
+        width: u32,
+        height: u32,
+        margin: i32,
+    ) -> Option<(i32, i32)> {
+        let (h, v) = self.aligns()?;
+        Some((
+            align(h, monitor.x, monitor.width, width, margin),
+            align(v, monitor.y, monitor.height, height, margin),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const MONITOR: Rect = Rect {
+        x: 1920,
+        y: 0,
+        width: 1280,
+        height: 720,
+    };
+
+    #[test]
+    fn test_position() {
+        assert_eq!(Anchor::Free.position(MONITOR, 200, 100, 10), None);
+        assert_eq!(
+            Anchor::TopLeft.position(MONITOR, 200, 100, 10),
+            Some((1930, 10))
+        );
+        assert_eq!(
+            Anchor::Center.position(MONITOR, 200, 100, 10),
+            Some((2460, 310))
+        );
+        assert_eq!(
+            Anchor::BottomRight.position(MONITOR, 200, 100, 10),
+            Some((2990, 610))
+        );
+        assert_eq!(
+            Anchor::Bottom.position(MONITOR, 200, 100, 10),
+            Some((2460, 610))
+        );
+    }
+
+    #[test]
+    fn test_monitor_at() {
+        let laptop = Rect {
+            x: 0,
+            y: 0,
+            width: 1920,
+            height: 1080,
+        };
+        let monitors = [laptop, MONITOR];
+        assert_eq!(monitor_at(&monitors, (100, 100)), Some(0));
+        assert_eq!(monitor_at(&monitors, (1920, 0)), Some(1));
+        assert_eq!(monitor_at(&monitors, (5000, 100)), Some(1));
+        assert_eq!(monitor_at(&[], (0, 0)), None);
+    }
+
+    #[test]
+    fn test_setup_key() {
+        let laptop = Rect {
+            x: 0,
+            y: 0,
+            width: 1920,
+            height: 1080,
+        };
+        assert_eq!(
+            setup_key(&[MONITOR, laptop]),
+            "1920x1080+0+0,1280x720+1920+0"
+        );
+        assert_eq!(setup_key(&[laptop, MONITOR]), setup_key(&[MONITOR, laptop]));
+        assert_ne!(setup_key(&[laptop]), setup_key(&[MONITOR, laptop]));
+    }
+
+    #[test]
+    fn test_clamp_onto() {
+        assert_eq!(clamp_onto(&[MONITOR], (2000, 100), 200, 100), (2000, 100));
+        // hanging off the bottom right
+        assert_eq!(clamp_onto(&[MONITOR], (3100, 700), 200, 100), (3000, 620));
+        // left on a monitor that is gone
+        assert_eq!(clamp_onto(&[MONITOR], (-500, 300), 200, 100), (1920, 300));
+        // larger than the monitor, the top left stays visible
+        assert_eq!(clamp_onto(&[MONITOR], (2000, 0), 2000, 100), (1920, 0));
+        assert_eq!(clamp_onto(&[], (-500, 300), 200, 100), (-500, 300));
+    }
+
+    #[test]
+    fn test_right_anchor_grows_left() {
+        let (narrow, _) = Anchor::Right.position(MONITOR, 200, 100, 10).unwrap();
+        let (wide, _) = Anchor::Right.position(MONITOR, 400, 100, 10).unwrap();
+        assert_eq!(narrow + 200, wide + 400);
+        let (left, _) = Anchor::Left.position(MONITOR, 400, 100, 10).unwrap();
+        assert_eq!(left, 1930);
+    }
+}
@@ -1,9 +1,250 @@
+use log::{debug, error, warn};
+use std::sync::{Arc, RwLock};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+use crate::aggregator::{Aggregator, STUCK_TIMEOUT};
+use crate::config::Config;
+use crate::error::Result;
+use crate::keyway::{KeyEvent, KeyState};
+use crate::recorder::{DeviceInfo, Recorder};
+use crate::sink::Sink;
+use crate::symbol::SymbolMap;
+
 #[cfg(target_os = "linux")]
 mod linux;
 #[cfg(target_os = "linux")]
-pub use linux::run_sender;
+pub use linux::Keyboards;
+
+#[cfg(target_os = "linux")]
+mod device;
+#[cfg(target_os = "linux")]
+pub use device::{EvdevBackend, EvdevBackend as NativeBackend};
 
 #[cfg(target_os = "windows")]
 mod windows;
 #[cfg(target_os = "windows")]
-pub use windows::run_sender;
+pub use windows::{HookBackend, HookBackend as NativeBackend, Keyboards};
+
+mod replay;
+pub use replay::ReplayBackend;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+// Delay before reopening a failed backend, doubled on every failure in a row.
+const RESTART_DELAY: Duration = Duration::from_secs(1);
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(30);
+
+/// A source of raw key events, either the devices of this machine or a
+/// recorded session.
+pub trait InputBackend {
+    /// The devices events will come from, read once before the first poll.
+    fn devices(&self) -> Vec<DeviceInfo>;
+    /// Waits up to `timeout` for key events. An error means the backend is
+    /// unusable and has to be opened again.
+    fn poll(&mut self, timeout: Duration) -> Result<Vec<KeyEvent>>;
+    /// Whether the backend has run out of events.
+    fn is_finished(&self) -> bool {
+        false
+    }
+}
+
+/// Feeds the events of `backend` through the keymap and the aggregator and
+/// hands the displayed groups to `sink` whenever they change. Returns once
+/// the backend is finished and the display has timed out, or with the error
+/// of the backend.
+pub fn run_pipeline(
+    backend: &mut dyn InputBackend,
+    config: &watch::Receiver<Config>,
+    symbols: &RwLock<SymbolMap>,
+    recorder: &mut Option<Recorder>,
+    sink: &mut dyn Sink,
+) -> Result<()> {
+    let devices = backend.devices();
+    if let Some(recorder) = recorder.as_mut() {
+        for device in devices.iter() {
+            recorder
+                .device(device.clone())
+                .unwrap_or_else(|e| warn!("Failed to record device: {e}"));
+        }
+    }
+    let mut keyboards = Keyboards::new(&devices)?;
+    let mut aggregator = Aggregator::new(STUCK_TIMEOUT);
+    let mut sent = None;
+    let mut modifiers = devices.first().map(|device| keyboards.modifiers(device.id));
+    if let Some(modifiers) = modifiers.as_ref() {
+        sink.modifiers(modifiers);
+    }
+    loop {
+        let (timeout, sides, layout) = {
+            let config = config.borrow();
+            (
+                Duration::from_millis(config.behavior.timeout as u64),
+                config.behavior.modifierside,
+                config.windowappearance.layout,
+            )
+        };
+        for event in backend.poll(POLL_INTERVAL)? {
+            if let Some(recorder) = recorder.as_mut() {
+                recorder
+                    .key(&event)
+                    .unwrap_or_else(|e| warn!("Failed to record key: {e}"));
+            }
+            let now = Instant::now();
+            let key = (event.device, event.virtkey);
+            match event.state {
+                KeyState::Release => aggregator.release(key, now),
+                KeyState::Press | KeyState::Repeat => aggregator.press(key, now),
+            }
+            if let Some(group) = keyboards.handle(&event, &symbols.read().unwrap(), sides) {
+                aggregator.push(group);
+            }
+            let changed = keyboards.modifiers(event.device);
+            if modifiers != Some(changed) {
+                sink.modifiers(&changed);
+                modifiers = Some(changed);
+            }
+        }
+        for (device, virtkey) in aggregator.expire(Instant::now(), timeout) {
+            debug!("Releasing stuck key {virtkey:#x} on device {device}");
+            keyboards.release(device, virtkey);
+        }
+        let shown = aggregator.arranged(layout);
+        if sent.as_ref() != Some(&shown) {
+            debug!("Keystrokes: {:?}", shown);
+            sink.send(&shown)
+                .unwrap_or_else(|e| warn!("Failed to send keystrokes: {e}"));
+            sent = Some(shown);
+        }
+        if backend.is_finished() && aggregator.groups().is_empty() {
+            debug!("Input finished");
+            return Ok(());
+        }
+    }
+}
+
+/// Runs the pipeline on a thread of its own and returns its handle. Whenever
+/// the backend fails the error is reported to `sink` and a new backend is
+/// opened with `open`, waiting longer after each failure in a row.
+pub fn run_sender<F>(
+    mut open: F,
+    config: watch::Receiver<Config>,
+    symbols: Arc<RwLock<SymbolMap>>,
+    mut recorder: Option<Recorder>,
+    mut sink: Box<dyn Sink + Send>,
+) -> JoinHandle<()>
+where
+    F: FnMut() -> Result<Box<dyn InputBackend>> + Send + 'static,
+{
+    std::thread::spawn(move || {
+        let mut delay = RESTART_DELAY;
+        loop {
+            let started = Instant::now();
+            let result = open().and_then(|mut backend| {
+                run_pipeline(
+                    backend.as_mut(),
+                    &config,
+                    &symbols,
+                    &mut recorder,
+                    sink.as_mut(),
+                )
+            });
+            let Err(e) = result else {
+                return;
+            };
+            if started.elapsed() > MAX_RESTART_DELAY {
+                delay = RESTART_DELAY;
+            }
+            error!("Capture failed, restarting in {delay:?}: {e}");
+            sink.error(&e);
+            sink.send(&[])
+                .unwrap_or_else(|e| warn!("Failed to send keystrokes: {e}"));
+            std::thread::sleep(delay);
+            delay = (delay * 2).min(MAX_RESTART_DELAY);
+        }
+    })
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod test {
+    use super::*;
+    use crate::error::Error;
+    use crate::keyway::{Key, KeyClass};
+    use crate::recorder::{Header, Recording};
+    use crate::testutil::key;
+
+    fn config(timeout: u32) -> watch::Receiver<Config> {
+        let mut config = Config::default();
+        config.behavior.timeout = timeout;
+        watch::Sender::new(config).subscribe()
+    }
+
+    #[test]
+    fn test_replay_through_pipeline() {
+        // Ctrl+c, then a with Ctrl released
+        let events = vec![
+            key(0, 29, KeyState::Press),
+            key(10, 46, KeyState::Press),
+            key(20, 46, KeyState::Release),
+            key(30, 29, KeyState::Release),
+            key(40, 30, KeyState::Press),
+            key(50, 30, KeyState::Release),
+        ];
+        let recording = Recording {
+            header: Header::now(),
+            devices: vec![DeviceInfo {
+                id: 0,
+                name: "test".to_string(),
+                keymap: None,
+                locks: Vec::new(),
+            }],
+            events,
+        };
+        let mut backend = ReplayBackend::new(recording, 10.0).unwrap();
+        let mut shown = Vec::new();
+        run_pipeline(
+            &mut backend,
+            &config(100),
+            &RwLock::new(SymbolMap::new()),
+            &mut None,
+            &mut |groups: &[Vec<Key>]| {
+                if groups.len() > shown.len() {
+                    shown = groups.to_vec();
+                }
+            },
+        )
+        .unwrap();
+        let ctrl = Key::new("Ctrl", KeyClass::Modifier);
+        assert_eq!(
+            shown,
+            vec![
+                vec![ctrl.clone()],
+                vec![ctrl, Key::new("c", KeyClass::Printable)],
+                vec![Key::new("a", KeyClass::Printable)]
+            ]
+        );
+    }
+
+    struct Failing;
+
+    impl InputBackend for Failing {
+        fn devices(&self) -> Vec<DeviceInfo> {
+            Vec::new()
+        }
+        fn poll(&mut self, _timeout: Duration) -> Result<Vec<KeyEvent>> {
+            Err(Error::NoDevices)
+        }
+    }
+
+    #[test]
+    fn test_backend_error() {
+        let result = run_pipeline(
+            &mut Failing,
+            &config(100),
+            &RwLock::new(SymbolMap::new()),
+            &mut None,
+            &mut |_: &[Vec<Key>]| {},
+        );
+        assert!(matches!(result, Err(Error::NoDevices)));
+    }
+}
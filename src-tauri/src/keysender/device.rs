@@ -0,0 +1,220 @@
+use evdev::{Device, Key, LedType};
+use log::{debug, warn};
+use mio::{unix::SourceFd, Events, Interest, Poll, Token};
+use std::collections::HashSet;
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use xkbcommon::xkb;
+
+use super::linux::{default_keymap, xkb_keycode};
+use super::InputBackend;
+use crate::error::{Error, Result};
+use crate::keyway::{KeyEvent, KeyState};
+use crate::modifier::ModifierKey;
+use crate::recorder::DeviceInfo;
+
+fn is_keyboard(dev: &Device) -> bool {
+    let has_key = dev.supported_events().contains(evdev::EventType::KEY);
+    let has_misc = dev.supported_events().contains(evdev::EventType::MISC);
+    let has_rpt = dev.supported_events().contains(evdev::EventType::REPEAT);
+    has_key && has_misc && has_rpt
+}
+
+fn get_allkeyabords() -> Vec<(PathBuf, Device)> {
+    let devices = evdev::enumerate()
+        .filter(|x| {
+            let dev = &x.1;
+            is_keyboard(dev)
+        })
+        .collect::<Vec<_>>();
+    devices
+}
+
+const KEY_STATE_RELEASE: i32 = 0;
+const KEY_STATE_PREESS: i32 = 1;
+const KEY_STATE_REPEAT: i32 = 2;
+// Held keys are checked against the device this often, so a release lost
+// while the session was locked does not leave the key down forever.
+const RESYNC_INTERVAL: Duration = Duration::from_secs(1);
+// A loop iteration this late means the process was suspended.
+const RESYNC_GAP: Duration = Duration::from_millis(500);
+
+fn key_event(start: Instant, device: usize, code: u16, state: KeyState) -> KeyEvent {
+    KeyEvent {
+        time: start.elapsed().as_millis() as u64,
+        device: device as u32,
+        scancode: code as u32,
+        virtkey: xkb_keycode(code).raw(),
+        state,
+    }
+}
+
+/// Reads key events from every keyboard under /dev/input. A keyboard that
+/// fails is dropped, the backend fails once none are left.
+pub struct EvdevBackend {
+    devices: Vec<Option<(PathBuf, Device)>>,
+    held: Vec<HashSet<u16>>,
+    poll: Poll,
+    events: Events,
+    start: Instant,
+    last_poll: Instant,
+    last_resync: Instant,
+}
+
+impl EvdevBackend {
+    pub fn open() -> Result<Self> {
+        EvdevBackend::with_devices(get_allkeyabords())
+    }
+    pub fn with_devices(devices: Vec<(PathBuf, Device)>) -> Result<Self> {
+        if devices.is_empty() {
+            return Err(Error::NoDevices);
+        }
+        let poll = Poll::new()?;
+        for (i, (p, d)) in devices.iter().enumerate() {
+            debug!("Listening on {:?}", p);
+            poll.registry()
+                .register(&mut SourceFd(&d.as_raw_fd()), Token(i), Interest::READABLE)
+                .map_err(|source| Error::Device {
+                    path: p.clone(),
+                    source,
+                })?;
+        }
+        Ok(EvdevBackend {
+            held: vec![HashSet::new(); devices.len()],
+            devices: devices.into_iter().map(Some).collect(),
+            poll,
+            events: Events::with_capacity(32),
+            start: Instant::now(),
+            last_poll: Instant::now(),
+            last_resync: Instant::now(),
+        })
+    }
+    /// Stops reading from a failed device and releases the keys it held.
+    fn remove(&mut self, i: usize, error: Error) -> Vec<KeyEvent> {
+        warn!("Dropping keyboard: {error}");
+        if let Some((_, d)) = self.devices[i].take() {
+            let _ = self
+                .poll
+                .registry()
+                .deregister(&mut SourceFd(&d.as_raw_fd()));
+        }
+        self.held[i]
+            .drain()
+            .map(|code| key_event(self.start, i, code, KeyState::Release))
+            .collect()
+    }
+    /// Brings the held keys in line with the devices' current key state and
+    /// returns events for the presses and releases that were missed.
+    fn resync(&mut self) -> Vec<KeyEvent> {
+        let mut missed = Vec::new();
+        for (i, (device, held)) in self.devices.iter().zip(self.held.iter_mut()).enumerate() {
+            let Some((p, d)) = device else {
+                continue;
+            };
+            let Ok(pressed) = d.get_key_state() else {
+                continue;
+            };
+            for code in held.clone() {
+                if !pressed.contains(Key::new(code)) {
+                    debug!("Resync: release missed for {code} on {:?}", p);
+                    held.remove(&code);
+                    missed.push(key_event(self.start, i, code, KeyState::Release));
+                }
+            }
+            for key in pressed.iter() {
+                if held.insert(key.code()) {
+                    debug!("Resync: press missed for {} on {:?}", key.code(), p);
+                    missed.push(key_event(self.start, i, key.code(), KeyState::Press));
+                }
+            }
+        }
+        missed
+    }
+}
+
+impl InputBackend for EvdevBackend {
+    fn devices(&self) -> Vec<DeviceInfo> {
+        let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+        let keymap = default_keymap(&context).map(|k| k.get_as_string(xkb::KEYMAP_FORMAT_TEXT_V1));
+        self.devices
+            .iter()
+            .enumerate()
+            .filter_map(|(i, device)| device.as_ref().map(|(_, d)| (i, d)))
+            .map(|(i, d)| {
+                let leds = d.get_led_state().ok();
+                let locks = [
+                    (LedType::LED_CAPSL, ModifierKey::CapsLock),
+                    (LedType::LED_NUML, ModifierKey::NumLock),
+                    (LedType::LED_SCROLLL, ModifierKey::ScrollLock),
+                ]
+                .into_iter()
+                .filter(|(led, _)| leds.as_ref().is_some_and(|l| l.contains(*led)))
+                .map(|(_, key)| key)
+                .collect();
+                DeviceInfo {
+                    id: i as u32,
+                    name: d.name().unwrap_or("unknown").to_string(),
+                    keymap: keymap.clone(),
+                    locks,
+                }
+            })
+            .collect()
+    }
+    fn poll(&mut self, timeout: Duration) -> Result<Vec<KeyEvent>> {
+        let mut keys = Vec::new();
+        match self.poll.poll(&mut self.events, Some(timeout)) {
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => return Ok(keys),
+            result => result?,
+        }
+        let ready = self.events.iter().map(|e| e.token().0).collect::<Vec<_>>();
+        for i in ready {
+            let Some((p, d)) = &mut self.devices[i] else {
+                continue;
+            };
+            let fetched = match d.fetch_events().map(|e| e.collect::<Vec<_>>()) {
+                Ok(fetched) => fetched,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+                Err(source) => {
+                    let error = Error::Device {
+                        path: p.clone(),
+                        source,
+                    };
+                    keys.extend(self.remove(i, error));
+                    continue;
+                }
+            };
+            for e in fetched {
+                if let evdev::InputEventKind::Key(key) = e.kind() {
+                    let code = key.code();
+                    let state = match e.value() {
+                        KEY_STATE_RELEASE => {
+                            self.held[i].remove(&code);
+                            KeyState::Release
+                        }
+                        KEY_STATE_REPEAT => KeyState::Repeat,
+                        _ => {
+                            self.held[i].insert(code);
+                            KeyState::Press
+                        }
+                    };
+                    keys.push(key_event(self.start, i, code, state));
+                }
+            }
+        }
+        let now = Instant::now();
+        let any_held = self.held.iter().any(|h| !h.is_empty());
+        if (any_held && now - self.last_resync > RESYNC_INTERVAL)
+            || now - self.last_poll > RESYNC_GAP
+        {
+            keys.extend(self.resync());
+            self.last_resync = now;
+        }
+        self.last_poll = now;
+        if self.devices.iter().all(Option::is_none) {
+            return Err(Error::NoDevices);
+        }
+        Ok(keys)
+    }
+}
@@ -1,179 +1,576 @@
-use evdev::Device;
-use log::debug;
-use mio::{unix::SourceFd, Events, Interest, Poll, Token};
-use std::borrow::Borrow;
-use std::os::unix::io::AsRawFd;
-use std::path::PathBuf;
-use std::sync::{Arc, RwLock};
-use std::time::{Duration, Instant};
-use tauri::{AppHandle, Manager};
+use log::warn;
+use std::collections::hash_map::{Entry, HashMap};
 use xkbcommon::xkb;
 
-use crate::keyway::Keystroke;
+use crate::error::{Error, Result};
+use crate::keyway::{Key, KeyClass, KeyEvent, KeyState};
+use crate::modifier::{ModifierKey, ModifierState, Modifiers, Side};
+use crate::recorder::DeviceInfo;
+use crate::symbol::{self, SymbolMap};
 
-fn is_keyboard(dev: &Device) -> bool {
-    let has_key = dev.supported_events().contains(evdev::EventType::KEY);
-    let has_misc = dev.supported_events().contains(evdev::EventType::MISC);
-    let has_rpt = dev.supported_events().contains(evdev::EventType::REPEAT);
-    has_key && has_misc && has_rpt
-}
+const KEY_OFFSET: u16 = 8;
+// the evdev codes of mouse buttons, see linux/input-event-codes.h
+const BTN_LEFT: u32 = 0x110;
+const BTN_TASK: u32 = 0x117;
 
-fn get_allkeyabords() -> Vec<(PathBuf, Device)> {
-    let devices = evdev::enumerate()
-        .filter(|x| {
-            let dev = &x.1;
-            is_keyboard(dev)
-        })
-        .collect::<Vec<_>>();
-    devices
+pub(super) fn xkb_keycode(code: u16) -> xkb::Keycode {
+    (u32::from(code) + u32::from(KEY_OFFSET)).into()
 }
 
-const KEY_STATE_RELEASE: i32 = 0;
-const KEY_STATE_PREESS: i32 = 1;
-const KEY_STATE_REPEAT: i32 = 2;
-const KEY_OFFSET: u16 = 8;
+// The keymap configured through the XKB_DEFAULT_* environment variables.
+pub(super) fn default_keymap(context: &xkb::Context) -> Option<xkb::Keymap> {
+    xkb::Keymap::new_from_names(context, "", "", "", "", None, xkb::COMPILE_NO_FLAGS)
+}
 
 struct Keyboard {
     context: xkb::Context,
     keymap: xkb::Keymap,
     state: xkb::State,
     // compose_state: xkb::compose::State,
-    path: PathBuf,
+    modifiers: ModifierState,
+    // evdev codes of the held keys producing Hyper_L or Hyper_R
+    hyper: Vec<u16>,
 }
 
 impl Keyboard {
-    fn new(p: &PathBuf) -> Self {
+    fn new(device: &DeviceInfo) -> Result<Self> {
         let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
-        let keymap =
-            xkb::Keymap::new_from_names(&context, "", "", "", "", None, xkb::COMPILE_NO_FLAGS)
-                .unwrap();
+        let keymap = device
+            .keymap
+            .as_ref()
+            .and_then(|keymap| {
+                xkb::Keymap::new_from_string(
+                    &context,
+                    keymap.clone(),
+                    xkb::KEYMAP_FORMAT_TEXT_V1,
+                    xkb::COMPILE_NO_FLAGS,
+                )
+            })
+            .or_else(|| default_keymap(&context))
+            .ok_or_else(|| Error::Keymap(format!("of {}", device.name)))?;
         let state = xkb::State::new(&keymap);
-        let path = p.clone();
         // let compose_state = xkb::compose::State::new();
-        Keyboard {
+        let mut keyboard = Keyboard {
             context,
             keymap,
             state,
-            path,
+            modifiers: ModifierState::new(),
+            hyper: Vec::new(),
+        };
+        keyboard.seed_locks(&device.locks);
+        Ok(keyboard)
+    }
+    fn seed_locks(&mut self, locks: &[ModifierKey]) {
+        for key in [
+            ModifierKey::CapsLock,
+            ModifierKey::NumLock,
+            ModifierKey::ScrollLock,
+        ] {
+            self.modifiers.set_locked(key, locks.contains(&key));
         }
+        // xkb starts with no locked modifiers, so carry the locks over
+        let mut locked = 0;
+        for (name, key) in [
+            (xkb::MOD_NAME_CAPS, ModifierKey::CapsLock),
+            (xkb::MOD_NAME_NUM, ModifierKey::NumLock),
+        ] {
+            let index = self.keymap.mod_get_index(name);
+            if index != xkb::MOD_INVALID && self.modifiers.is_locked(key) {
+                locked |= 1 << index;
+            }
+        }
+        self.state.update_mask(0, 0, locked, 0, 0, 0);
     }
     fn is_repeats(&self, keycode: xkb::Keycode) -> bool {
         self.keymap.key_repeats(keycode)
     }
-    fn mod_name_is_active<S: Borrow<str> + ?Sized>(
-        &self,
-        name: &S,
-        type_: xkb::StateComponent,
-    ) -> bool {
-        self.state.mod_name_is_active(name, type_)
+    fn is_hyper(&self, keycode: xkb::Keycode) -> bool {
+        matches!(
+            self.get_keysym_name(keycode).as_str(),
+            "Hyper_L" | "Hyper_R"
+        )
     }
-    fn update(
-        &mut self,
-        keycode: xkb::Keycode,
-        direction: xkb::KeyDirection,
-    ) -> xkb::StateComponent {
-        self.state.update_key(keycode, direction)
+    fn key_down(&mut self, code: u16) {
+        if self.is_hyper(xkb_keycode(code)) {
+            if !self.hyper.contains(&code) {
+                self.hyper.push(code);
+            }
+        } else if let Some(key) = ModifierKey::from_evdev(code) {
+            self.modifiers.update(key, true);
+        }
+        self.state
+            .update_key(xkb_keycode(code), xkb::KeyDirection::Down);
+    }
+    fn key_up(&mut self, code: u16) {
+        if self.hyper.contains(&code) {
+            self.hyper.retain(|held| *held != code);
+        } else if let Some(key) = ModifierKey::from_evdev(code) {
+            self.modifiers.update(key, false);
+        }
+        self.state
+            .update_key(xkb_keycode(code), xkb::KeyDirection::Up);
+    }
+    fn is_mod_active(&self, name: &str) -> bool {
+        self.state
+            .mod_name_is_active(name, xkb::STATE_MODS_EFFECTIVE)
+    }
+    fn is_mod_locked(&self, name: &str) -> bool {
+        self.state.mod_name_is_active(name, xkb::STATE_MODS_LOCKED)
+    }
+    /// The effective modifiers according to xkb, with the side taken from the
+    /// physical keys that are held.
+    ///
+    /// The stock keymaps put the Hyper virtual modifier on Mod4 with Super and
+    /// xkb never reports a virtual modifier as active, so Hyper is told apart
+    /// by the keysyms of the held keys instead.
+    fn get_modifiers(&self) -> Modifiers {
+        let physical = self.modifiers.modifiers();
+        let side = |active: bool, held: Option<Side>| active.then(|| held.unwrap_or(Side::Any));
+        let hyper = !self.hyper.is_empty();
+        let logo = self.is_mod_active(xkb::MOD_NAME_LOGO) && !(hyper && physical.logo.is_none());
+        Modifiers {
+            shift: side(self.is_mod_active(xkb::MOD_NAME_SHIFT), physical.shift),
+            ctrl: side(self.is_mod_active(xkb::MOD_NAME_CTRL), physical.ctrl),
+            alt: side(self.is_mod_active(xkb::MOD_NAME_ALT), physical.alt),
+            logo: side(logo, physical.logo),
+            altgr: self.is_mod_active(xkb::MOD_NAME_ISO_LEVEL3_SHIFT),
+            hyper,
+            caps_lock: self.is_mod_locked(xkb::MOD_NAME_CAPS),
+            num_lock: self.is_mod_locked(xkb::MOD_NAME_NUM),
+            // the keymaps have no Scroll Lock modifier
+            scroll_lock: physical.scroll_lock,
+        }
     }
     fn get_string(&self, keycode: xkb::Keycode) -> String {
         self.state.key_get_utf8(keycode)
     }
+    fn get_keysym_name(&self, keycode: xkb::Keycode) -> String {
+        xkb::keysym_get_name(self.state.key_get_one_sym(keycode))
+    }
+    fn get_key(&self, physcode: u32, keycode: xkb::Keycode, symbols: &SymbolMap) -> Key {
+        let name = self.get_keysym_name(keycode);
+        let class = if (BTN_LEFT..=BTN_TASK).contains(&physcode) {
+            KeyClass::Mouse
+        } else {
+            symbol::keysym_class(&name)
+        };
+        let mut text = self.get_string(keycode);
+        if text.trim().is_empty() || text.chars().any(char::is_control) {
+            text = symbol::keysym_label(&name).to_string();
+        }
+        match symbols.lookup(&name, physcode) {
+            Some(label) => Key::labeled(label, class),
+            None => Key::new(text, class),
+        }
+    }
+}
+
+/// The xkb state of every device, turning key events into display groups.
+pub struct Keyboards {
+    keyboards: HashMap<u32, Keyboard>,
 }
 
-pub fn run_sender(timeout: Arc<RwLock<u32>>, apphandle: AppHandle, label: String, event: String) {
-    let recv = std::thread::spawn(move || {
-        let mut devices = get_allkeyabords();
-        let mut tokens = vec![];
-        let mut keyboards = Vec::<Keyboard>::new();
-        for (p, _d) in devices.iter() {
-            keyboards.push(Keyboard::new(p));
+impl Keyboards {
+    pub fn new(devices: &[DeviceInfo]) -> Result<Self> {
+        let keyboards = devices
+            .iter()
+            .map(|device| Ok((device.id, Keyboard::new(device)?)))
+            .collect::<Result<_>>()?;
+        Ok(Keyboards { keyboards })
+    }
+    fn get(&mut self, device: u32) -> Option<&mut Keyboard> {
+        let entry = match self.keyboards.entry(device) {
+            Entry::Occupied(entry) => return Some(entry.into_mut()),
+            Entry::Vacant(entry) => entry,
+        };
+        let info = DeviceInfo {
+            id: device,
+            name: "unknown".to_string(),
+            keymap: None,
+            locks: Vec::new(),
+        };
+        match Keyboard::new(&info) {
+            Ok(keyboard) => Some(entry.insert(keyboard)),
+            Err(e) => {
+                warn!("Ignoring device {device}: {e}");
+                None
+            }
+        }
+    }
+    /// Applies `event` and returns the group to display for it, if any.
+    pub fn handle(
+        &mut self,
+        event: &KeyEvent,
+        symbols: &SymbolMap,
+        sides: bool,
+    ) -> Option<Vec<Key>> {
+        let keyboard = self.get(event.device)?;
+        // evdev codes fit in 16 bits, anything else did not come from evdev
+        let code = u16::try_from(event.scancode).ok()?;
+        let keycode = xkb_keycode(code);
+        let hyper = keyboard.is_hyper(keycode);
+        let modifier = ModifierKey::from_evdev(code).filter(|_| !hyper);
+        match event.state {
+            KeyState::Release => {
+                keyboard.key_up(code);
+                return None;
+            }
+            KeyState::Repeat if hyper || modifier.is_some() || keyboard.is_repeats(keycode) => {
+                return None
+            }
+            _ => keyboard.key_down(code),
         }
-        for i in 0..devices.len() {
-            tokens.push(Token(i));
+        let mut keys = keyboard.get_modifiers().keys(sides);
+        // held modifiers come from the labels above, lock keys show as keys
+        if !hyper && modifier.is_none_or(|k| k.is_lock()) {
+            keys.push(keyboard.get_key(event.scancode, keycode, symbols));
         }
-        let mut poll = Poll::new().unwrap();
-        for (i, (_, d)) in devices.iter().enumerate() {
-            poll.registry()
-                .register(&mut SourceFd(&d.as_raw_fd()), tokens[i], Interest::READABLE)
-                .unwrap();
+        Some(keys)
+    }
+    /// The modifiers and lock toggles of `device`.
+    pub fn modifiers(&self, device: u32) -> Modifiers {
+        self.keyboards
+            .get(&device)
+            .map(Keyboard::get_modifiers)
+            .unwrap_or_default()
+    }
+    /// Releases a key whose release event never arrived.
+    pub fn release(&mut self, device: u32, virtkey: u32) {
+        let Some(code) = u16::try_from(virtkey)
+            .ok()
+            .and_then(|keycode| keycode.checked_sub(KEY_OFFSET))
+        else {
+            warn!("Ignoring release of keycode {virtkey} below {KEY_OFFSET}");
+            return;
+        };
+        if let Some(keyboard) = self.get(device) {
+            keyboard.key_up(code);
         }
-        let mut events = Events::with_capacity(32);
-        let mut keystrokes = Vec::<Keystroke>::new();
-        let mut timestamp = Instant::now();
-        '_keysend_loop: loop {
-            let timeout = Duration::from_millis(*timeout.read().unwrap() as u64);
-            poll.poll(&mut events, Some(Duration::from_millis(50)))
-                .unwrap();
-            for event in events.iter() {
-                match event.token() {
-                    Token(i) if (0..devices.len()).contains(&i) => {
-                        let (_, ref mut d) = devices.get_mut(i).unwrap();
-                        let keyboard = keyboards.get_mut(i).unwrap();
-                        for e in d.fetch_events().unwrap() {
-                            match e.kind() {
-                                evdev::InputEventKind::Key(keycode) => {
-                                    timestamp = Instant::now();
-                                    let physcode = keycode.0 as u32;
-                                    let keycode: xkb::Keycode = (keycode.0 + KEY_OFFSET).into();
-                                    let keystate = e.value();
-                                    if keystate == KEY_STATE_REPEAT && keyboard.is_repeats(keycode)
-                                    {
-                                        continue;
-                                    }
-                                    let changes = if keystate == KEY_STATE_RELEASE {
-                                        keyboard.update(keycode, xkb::KeyDirection::Up)
-                                    } else {
-                                        let ret = keyboard.update(keycode, xkb::KeyDirection::Down);
-                                        let keystroke = Keystroke::new(
-                                            physcode,
-                                            keycode.raw(),
-                                            keyboard.get_string(keycode),
-                                        );
-                                        keystrokes.push(keystroke);
-                                        ret
-                                    };
-
-                                    if keyboard.mod_name_is_active(
-                                        xkb::MOD_NAME_CTRL,
-                                        xkb::STATE_MODS_EFFECTIVE,
-                                    ) {
-                                        let keystroke = Keystroke::new(
-                                            physcode,
-                                            keycode.raw(),
-                                            keyboard.get_string(keycode),
-                                        );
-                                        keystrokes.push(keystroke);
-                                    }
-                                    if keyboard.mod_name_is_active(
-                                        xkb::MOD_NAME_SHIFT,
-                                        xkb::STATE_MODS_EFFECTIVE,
-                                    ) {
-                                        let keystroke = Keystroke::new(
-                                            physcode,
-                                            keycode.raw(),
-                                            keyboard.get_string(keycode),
-                                        );
-                                        keystrokes.push(keystroke);
-                                    }
-                                }
-                                _ => (),
-                            }
-                        }
-                    }
-                    _ => {
-                        unreachable!()
-                    }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fmt::Write;
+    use std::fs;
+    use std::path::Path;
+
+    // evdev codes of the keys used by the golden sequences
+    const KEYS: &[(&str, u16)] = &[
+        ("esc", 1),
+        ("1", 2),
+        ("2", 3),
+        ("6", 7),
+        ("minus", 12),
+        ("equal", 13),
+        ("backspace", 14),
+        ("tab", 15),
+        ("q", 16),
+        ("w", 17),
+        ("e", 18),
+        ("y", 21),
+        ("u", 22),
+        ("o", 24),
+        ("bracketleft", 26),
+        ("bracketright", 27),
+        ("enter", 28),
+        ("ctrl", 29),
+        ("a", 30),
+        ("s", 31),
+        ("c", 46),
+        ("semicolon", 39),
+        ("apostrophe", 40),
+        ("grave", 41),
+        ("shift", 42),
+        ("backslash", 43),
+        ("z", 44),
+        ("m", 50),
+        ("comma", 51),
+        ("slash", 53),
+        ("rightshift", 54),
+        ("alt", 56),
+        ("space", 57),
+        ("capslock", 58),
+        ("f1", 59),
+        ("numlock", 69),
+        ("kp7", 71),
+        ("less", 86),
+        ("rightctrl", 97),
+        ("altgr", 100),
+        ("home", 102),
+        ("up", 103),
+        ("pagedown", 109),
+        ("delete", 111),
+        ("super", 125),
+    ];
+
+    struct Case {
+        name: &'static str,
+        layout: &'static str,
+        variant: &'static str,
+        sides: bool,
+        keys: &'static str,
+    }
+
+    // In `keys`, `+k` presses k, `-k` releases it, `*k` is an auto-repeat of k
+    // and a bare `k` is a press followed by a release.
+    const CASES: &[Case] = &[
+        Case {
+            name: "us",
+            layout: "us",
+            variant: "",
+            sides: false,
+            keys: "a +shift a 1 -shift capslock a capslock +ctrl c -ctrl +super enter -super \
+                   +a *a *a -a +shift *shift -shift space tab backspace esc f1 home up pagedown delete",
+        },
+        Case {
+            name: "us_sides",
+            layout: "us",
+            variant: "",
+            sides: true,
+            keys: "+ctrl +shift a -shift -ctrl +rightctrl +rightshift a -rightshift -rightctrl \
+                   +alt +rightctrl a -rightctrl -alt",
+        },
+        Case {
+            name: "us_intl",
+            layout: "us",
+            variant: "intl",
+            sides: false,
+            keys: "apostrophe e +shift 6 -shift o grave a +altgr q y -altgr \
+                   +altgr +shift s -shift -altgr",
+        },
+        Case {
+            name: "de",
+            layout: "de",
+            variant: "",
+            sides: false,
+            keys: "y z semicolon apostrophe bracketleft +shift 2 minus -shift \
+                   +altgr q e m less -altgr equal e",
+        },
+        Case {
+            name: "fr",
+            layout: "fr",
+            variant: "",
+            sides: false,
+            keys: "q a w z m 1 +shift 1 -shift 2 bracketleft e +altgr e 2 -altgr",
+        },
+        Case {
+            name: "ru",
+            layout: "ru",
+            variant: "",
+            sides: false,
+            keys: "q w e +shift a -shift grave comma slash numlock kp7 numlock kp7",
+        },
+    ];
+
+    fn code(name: &str) -> u16 {
+        KEYS.iter()
+            .find(|(n, _)| *n == name)
+            .unwrap_or_else(|| panic!("unknown key {name}"))
+            .1
+    }
+
+    fn events(sequence: &str) -> Vec<(String, u16, KeyState)> {
+        let mut events = Vec::new();
+        for token in sequence.split_whitespace() {
+            let (state, name) = match token.split_at(1) {
+                ("+", name) => (Some(KeyState::Press), name),
+                ("-", name) => (Some(KeyState::Release), name),
+                ("*", name) => (Some(KeyState::Repeat), name),
+                _ => (None, token),
+            };
+            match state {
+                Some(state) => events.push((token.to_string(), code(name), state)),
+                None => {
+                    events.push((format!("+{name}"), code(name), KeyState::Press));
+                    events.push((format!("-{name}"), code(name), KeyState::Release));
                 }
             }
-            if !keystrokes.is_empty() && (Instant::now() - timestamp > timeout) {
-                keystrokes.clear();
-            }
-            if !keystrokes.is_empty() {
-                debug!("Keystrokes: {:?}", keystrokes);
+        }
+        events
+    }
+
+    fn keymap(layout: &str, variant: &str) -> Option<String> {
+        keymap_with_options(layout, variant, None)
+    }
+
+    fn keymap_with_options(layout: &str, variant: &str, options: Option<&str>) -> Option<String> {
+        let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+        let keymap = xkb::Keymap::new_from_names(
+            &context,
+            "evdev",
+            "pc105",
+            layout,
+            variant,
+            options.map(str::to_string),
+            xkb::COMPILE_NO_FLAGS,
+        )?;
+        Some(keymap.get_as_string(xkb::KEYMAP_FORMAT_TEXT_V1))
+    }
+
+    /// Skips a test for a layout the xkb data of this system lacks, or fails
+    /// it when KEYWAY_REQUIRE_XKB is set so a skip cannot pass unnoticed.
+    fn missing(layout: &str) {
+        if std::env::var_os("KEYWAY_REQUIRE_XKB").is_some() {
+            panic!("layout {layout} not available and KEYWAY_REQUIRE_XKB is set");
+        }
+        eprintln!("skipping: layout {layout} not available");
+    }
+
+    /// Runs `sequence` through a keyboard with `keymap` and returns one line
+    /// per event with the displayed group.
+    fn render(keymap: String, sequence: &str, sides: bool) -> String {
+        let device = DeviceInfo {
+            id: 0,
+            name: "golden".to_string(),
+            keymap: Some(keymap),
+            locks: Vec::new(),
+        };
+        let mut keyboards = Keyboards::new(&[device]).unwrap();
+        let symbols = SymbolMap::new();
+        let mut out = String::new();
+        for (token, code, state) in events(sequence) {
+            let event = KeyEvent {
+                time: 0,
+                device: 0,
+                scancode: code as u32,
+                virtkey: xkb_keycode(code).raw(),
+                state,
+            };
+            let shown = match keyboards.handle(&event, &symbols, sides) {
+                Some(group) => group
+                    .iter()
+                    .map(|key| key.label.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" + "),
+                None => "-".to_string(),
+            };
+            writeln!(out, "{token:<14}{shown}").unwrap();
+        }
+        out
+    }
+
+    /// Compares against `testdata/xkb/<name>.golden`. Set UPDATE_GOLDEN=1 to
+    /// rewrite the files after an intended change.
+    #[test]
+    fn test_golden() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/xkb");
+        let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+        for case in CASES {
+            let Some(keymap) = keymap(case.layout, case.variant) else {
+                missing(case.layout);
+                continue;
+            };
+            let rendered = render(keymap, case.keys, case.sides);
+            let path = dir.join(format!("{}.golden", case.name));
+            if update {
+                fs::create_dir_all(&dir).unwrap();
+                fs::write(&path, &rendered).unwrap();
+                continue;
             }
-            apphandle
-                .emit_to(&label, &event, keystrokes.clone())
-                .unwrap();
+            let golden = fs::read_to_string(&path)
+                .unwrap_or_else(|e| panic!("{}: {e}, run with UPDATE_GOLDEN=1", path.display()));
+            assert_eq!(
+                rendered,
+                golden,
+                "{} differs from {}",
+                case.name,
+                path.display()
+            );
         }
-    });
-    recv.join().expect("Failed join recv");
+    }
+
+    #[test]
+    fn test_foreign_codes() {
+        let Some(keymap) = keymap("us", "") else {
+            missing("us");
+            return;
+        };
+        let device = DeviceInfo {
+            id: 0,
+            name: "foreign".to_string(),
+            keymap: Some(keymap),
+            locks: Vec::new(),
+        };
+        let mut keyboards = Keyboards::new(&[device]).unwrap();
+        // Windows virtual-key codes are below the xkb offset
+        keyboards.release(0, 0x01);
+        let event = KeyEvent {
+            time: 0,
+            device: 0,
+            scancode: 0x10000,
+            virtkey: 0x10008,
+            state: KeyState::Press,
+        };
+        assert_eq!(keyboards.handle(&event, &SymbolMap::new(), false), None);
+    }
+
+    #[test]
+    fn test_locks() {
+        let Some(keymap) = keymap("us", "") else {
+            missing("us");
+            return;
+        };
+        let device = DeviceInfo {
+            id: 0,
+            name: "locks".to_string(),
+            keymap: Some(keymap),
+            locks: vec![ModifierKey::NumLock],
+        };
+        let mut keyboards = Keyboards::new(&[device]).unwrap();
+        let type_keys = |keyboards: &mut Keyboards, sequence| {
+            for (_, code, state) in events(sequence) {
+                let event = KeyEvent {
+                    time: 0,
+                    device: 0,
+                    scancode: code as u32,
+                    virtkey: xkb_keycode(code).raw(),
+                    state,
+                };
+                keyboards.handle(&event, &SymbolMap::new(), false);
+            }
+        };
+        let locks = |m: Modifiers| (m.caps_lock, m.num_lock, m.scroll_lock);
+        assert_eq!(locks(keyboards.modifiers(0)), (false, true, false));
+        type_keys(&mut keyboards, "capslock");
+        assert_eq!(locks(keyboards.modifiers(0)), (true, true, false));
+        type_keys(&mut keyboards, "+capslock *capslock -capslock numlock");
+        assert_eq!(locks(keyboards.modifiers(0)), (false, false, false));
+        assert_eq!(keyboards.modifiers(7), Modifiers::default());
+    }
+
+    #[test]
+    fn test_hyper() {
+        // altwin:hyper_win turns the Win keys into Hyper, which shares Mod4 with Super
+        let Some(keymap) = keymap_with_options("us", "", Some("altwin:hyper_win")) else {
+            missing("us");
+            return;
+        };
+        let rendered = render(
+            keymap,
+            "+super *super enter +shift a -shift -super a",
+            false,
+        );
+        let shown = rendered
+            .lines()
+            .map(|line| line[14..].trim_end())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            shown,
+            [
+                "Hyper",
+                "-",
+                "Hyper + Enter",
+                "-",
+                "Hyper + Shift",
+                "Hyper + Shift + A",
+                "-",
+                "-",
+                "-",
+                "a",
+                "-"
+            ]
+        );
+    }
 }
@@ -0,0 +1,138 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use super::InputBackend;
+use crate::error::{Error, Result};
+use crate::keyway::KeyEvent;
+use crate::recorder::{DeviceInfo, Recording};
+
+/// The slowest playback, which keeps the delay of any event time in range
+/// of a Duration.
+const MIN_SPEED: f64 = 0.01;
+
+/// Plays back a recorded session with the original timing divided by `speed`.
+pub struct ReplayBackend {
+    devices: Vec<DeviceInfo>,
+    events: VecDeque<KeyEvent>,
+    speed: f64,
+    start: Option<Instant>,
+}
+
+impl ReplayBackend {
+    /// Fails for a recording made on another platform, as the key codes of
+    /// Linux and Windows mean different keys. `speed` is raised to 0.01, a
+    /// speed that is not positive plays at 1.0.
+    pub fn new(recording: Recording, speed: f64) -> Result<Self> {
+        if recording.header.platform != std::env::consts::OS {
+            return Err(Error::Platform(recording.header.platform));
+        }
+        Ok(ReplayBackend {
+            devices: recording.devices,
+            events: recording.events.into(),
+            speed: if speed > 0.0 {
+                speed.max(MIN_SPEED)
+            } else {
+                1.0
+            },
+            start: None,
+        })
+    }
+    fn due_in(&self, event: &KeyEvent, elapsed: Duration) -> Duration {
+        Duration::from_secs_f64(event.time as f64 / 1000.0 / self.speed).saturating_sub(elapsed)
+    }
+    /// Pops the events that are due `elapsed` after the start of the replay.
+    fn take_due(&mut self, elapsed: Duration) -> Vec<KeyEvent> {
+        let mut due = Vec::new();
+        while let Some(event) = self.events.front() {
+            if !self.due_in(event, elapsed).is_zero() {
+                break;
+            }
+            due.extend(self.events.pop_front());
+        }
+        due
+    }
+}
+
+impl InputBackend for ReplayBackend {
+    fn devices(&self) -> Vec<DeviceInfo> {
+        self.devices.clone()
+    }
+    fn poll(&mut self, timeout: Duration) -> Result<Vec<KeyEvent>> {
+        let start = *self.start.get_or_insert_with(Instant::now);
+        let wait = match self.events.front() {
+            Some(event) => self.due_in(event, start.elapsed()).min(timeout),
+            None => timeout,
+        };
+        std::thread::sleep(wait);
+        Ok(self.take_due(start.elapsed()))
+    }
+    fn is_finished(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::keyway::KeyState;
+    use crate::recorder::Header;
+
+    fn recording(times: &[u64]) -> Recording {
+        let events = times
+            .iter()
+            .map(|time| KeyEvent {
+                time: *time,
+                device: 0,
+                scancode: 30,
+                virtkey: 38,
+                state: KeyState::Press,
+            })
+            .collect();
+        Recording {
+            header: Header::now(),
+            devices: Vec::new(),
+            events,
+        }
+    }
+
+    #[test]
+    fn test_take_due() {
+        let mut replay = ReplayBackend::new(recording(&[0, 100, 100, 400]), 1.0).unwrap();
+        assert_eq!(replay.take_due(Duration::ZERO).len(), 1);
+        assert!(replay.take_due(Duration::from_millis(99)).is_empty());
+        assert_eq!(replay.take_due(Duration::from_millis(100)).len(), 2);
+        assert!(!replay.is_finished());
+        assert_eq!(replay.take_due(Duration::from_secs(1)).len(), 1);
+        assert!(replay.is_finished());
+    }
+
+    #[test]
+    fn test_accelerated() {
+        let mut replay = ReplayBackend::new(recording(&[0, 1000, 2000]), 10.0).unwrap();
+        assert_eq!(replay.take_due(Duration::from_millis(100)).len(), 2);
+        let start = Instant::now();
+        let mut events = Vec::new();
+        while !replay.is_finished() {
+            events.extend(replay.poll(Duration::from_millis(50)).unwrap());
+        }
+        assert_eq!(events.len(), 1);
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_slowest() {
+        let mut replay = ReplayBackend::new(recording(&[0, u64::MAX]), 1e-300).unwrap();
+        assert_eq!(replay.take_due(Duration::ZERO).len(), 1);
+        assert_eq!(replay.take_due(Duration::MAX).len(), 1);
+    }
+
+    #[test]
+    fn test_other_platform() {
+        let mut other = recording(&[0]);
+        other.header.platform = "plan9".to_string();
+        assert!(matches!(
+            ReplayBackend::new(other, 1.0),
+            Err(Error::Platform(platform)) if platform == "plan9"
+        ));
+    }
+}
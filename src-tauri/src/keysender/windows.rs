@@ -1,11 +1,18 @@
-use crate::keyway::Keystroke;
+use super::InputBackend;
+use crate::error::{Error, Result};
+use crate::keyway::{self, KeyEvent, KeyState};
+use crate::modifier::{ModifierKey, ModifierState, Modifiers};
+use crate::recorder::DeviceInfo;
+use crate::symbol::SymbolMap;
+use crate::vkcode;
 
-use log::{debug, warn};
+use log::{error, warn};
 use std::collections::HashMap;
+use std::io;
 use std::sync::mpsc::{self, Receiver, Sender};
-use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::sync::{Mutex, OnceLock};
+use std::thread::JoinHandle;
 use std::time::{Duration, Instant};
-use tauri::{AppHandle, Manager};
 
 use windows::Win32::Foundation::*;
 use windows::Win32::UI::Input::KeyboardAndMouse::*;
@@ -27,11 +34,23 @@ struct Key {
 
 static RX: OnceLock<Mutex<Receiver<Key>>> = OnceLock::new();
 static TX: OnceLock<Mutex<Sender<Key>>> = OnceLock::new();
+// The thread running the hook, restarted by `HookBackend::open` once it died.
+static HOOK: Mutex<Option<JoinHandle<()>>> = Mutex::new(None);
 
 fn init_channel() {
-    let (tx, rx) = mpsc::channel();
-    RX.set(Mutex::new(rx)).unwrap();
-    TX.set(Mutex::new(tx)).unwrap();
+    if RX.get().is_none() {
+        let (tx, rx) = mpsc::channel();
+        let _ = RX.set(Mutex::new(rx));
+        let _ = TX.set(Mutex::new(tx));
+    }
+}
+
+// Panicking inside the hook would abort the process, so keys that cannot be
+// sent are dropped.
+fn send(key: Key) {
+    if let Some(tx) = TX.get().and_then(|tx| tx.lock().ok()) {
+        let _ = tx.send(key);
+    }
 }
 
 unsafe fn extract_rawkey(lparam: &LPARAM, keyaction: KeyAction) -> Key {
@@ -54,35 +73,15 @@ extern "system" fn keyboard_proc(ncode: i32, wparam: WPARAM, lparam: LPARAM) ->
         if ncode as u32 == HC_ACTION {
             match wparam.0 as u32 {
                 WM_KEYDOWN | WM_SYSKEYDOWN => {
-                    let tx = TX
-                        .get()
-                        .expect("Failed to get")
-                        .lock()
-                        .expect("Failed to lock")
-                        .clone();
-                    let key = extract_rawkey(&lparam, KeyAction::KEYDOWN);
-                    tx.send(key).expect("Failed send");
+                    send(extract_rawkey(&lparam, KeyAction::KEYDOWN));
                 }
                 WM_KEYUP | WM_SYSKEYUP => {
-                    let tx = TX
-                        .get()
-                        .expect("Failed to get")
-                        .lock()
-                        .expect("Failed to lock")
-                        .clone();
-                    let key = extract_rawkey(&lparam, KeyAction::KEYUP);
-                    tx.send(key).expect("Failed send");
+                    send(extract_rawkey(&lparam, KeyAction::KEYUP));
                 }
                 _ => {
-                    let tx = TX
-                        .get()
-                        .expect("Failed to get")
-                        .lock()
-                        .expect("Failed to lock")
-                        .clone();
                     let key = extract_rawkey(&lparam, KeyAction::OTHER);
                     warn!("Other {:?}", key);
-                    tx.send(key).expect("Failed send");
+                    send(key);
                 }
             }
         }
@@ -90,197 +89,160 @@ extern "system" fn keyboard_proc(ncode: i32, wparam: WPARAM, lparam: LPARAM) ->
     }
 }
 
-fn keyboad_hook() {
+fn keyboad_hook() -> windows::core::Result<()> {
     unsafe {
         let k_hook =
-            SetWindowsHookExA(WH_KEYBOARD_LL, Some(keyboard_proc), HINSTANCE::default(), 0)
-                .expect("Failed systemhok");
+            SetWindowsHookExA(WH_KEYBOARD_LL, Some(keyboard_proc), HINSTANCE::default(), 0)?;
         let mut message = MSG::default();
         while GetMessageA(&mut message, HWND::default(), 0, 0).into() {
-            TranslateMessage(&message).expect("Failed TranslateMessageA");
+            let _ = TranslateMessage(&message);
             DispatchMessageA(&message);
         }
-        if !k_hook.is_invalid() {
-            UnhookWindowsHookEx(k_hook).expect("Failed unhook");
-        } else {
-            panic!("Hook paniced");
-        }
+        UnhookWindowsHookEx(k_hook)
     }
 }
 
-fn initialize_keymap() -> [Option<&'static str>; 256] {
-    let mut keymap = [None; 256];
-    keymap[VK_LBUTTON.0 as usize] = Some("LBUTTON");
-    keymap[VK_RBUTTON.0 as usize] = Some("RBUTTON");
-    keymap[VK_BACK.0 as usize] = Some("Backspace");
-    keymap[VK_TAB.0 as usize] = Some("Tab");
-    keymap[VK_RETURN.0 as usize] = Some("Enter");
-    keymap[VK_SHIFT.0 as usize] = Some("Shift");
-    keymap[VK_LSHIFT.0 as usize] = Some("Shift");
-    keymap[VK_RSHIFT.0 as usize] = Some("Shift");
-    keymap[VK_CONTROL.0 as usize] = Some("Ctrl");
-    keymap[VK_LCONTROL.0 as usize] = Some("Ctrl");
-    keymap[VK_RCONTROL.0 as usize] = Some("Ctrl");
-    keymap[VK_MENU.0 as usize] = Some("Alt");
-    keymap[VK_LMENU.0 as usize] = Some("Alt");
-    keymap[VK_RMENU.0 as usize] = Some("Alt");
-    keymap[VK_ESCAPE.0 as usize] = Some("Esc");
-    keymap[VK_SPACE.0 as usize] = Some("Space");
-    keymap[VK_PRIOR.0 as usize] = Some("PageUp");
-    keymap[VK_NEXT.0 as usize] = Some("PageDown");
-    keymap[VK_END.0 as usize] = Some("End");
-    keymap[VK_HOME.0 as usize] = Some("Home");
-    keymap[VK_LEFT.0 as usize] = Some("Left");
-    keymap[VK_RIGHT.0 as usize] = Some("Right");
-    keymap[VK_UP.0 as usize] = Some("Up");
-    keymap[VK_DOWN.0 as usize] = Some("Down");
-    keymap[VK_INSERT.0 as usize] = Some("Insert");
-    keymap[VK_DELETE.0 as usize] = Some("Delete");
-
-    // number and letters
-    keymap[VK_0.0 as usize] = Some("0");
-    keymap[VK_1.0 as usize] = Some("1");
-    keymap[VK_2.0 as usize] = Some("2");
-    keymap[VK_3.0 as usize] = Some("3");
-    keymap[VK_4.0 as usize] = Some("4");
-    keymap[VK_5.0 as usize] = Some("5");
-    keymap[VK_6.0 as usize] = Some("6");
-    keymap[VK_7.0 as usize] = Some("7");
-    keymap[VK_8.0 as usize] = Some("8");
-    keymap[VK_9.0 as usize] = Some("9");
-    keymap[VK_A.0 as usize] = Some("A");
-    keymap[VK_B.0 as usize] = Some("B");
-    keymap[VK_C.0 as usize] = Some("C");
-    keymap[VK_D.0 as usize] = Some("D");
-    keymap[VK_E.0 as usize] = Some("E");
-    keymap[VK_F.0 as usize] = Some("F");
-    keymap[VK_G.0 as usize] = Some("G");
-    keymap[VK_H.0 as usize] = Some("H");
-    keymap[VK_I.0 as usize] = Some("I");
-    keymap[VK_J.0 as usize] = Some("J");
-    keymap[VK_K.0 as usize] = Some("K");
-    keymap[VK_L.0 as usize] = Some("L");
-    keymap[VK_M.0 as usize] = Some("M");
-    keymap[VK_N.0 as usize] = Some("N");
-    keymap[VK_O.0 as usize] = Some("O");
-    keymap[VK_P.0 as usize] = Some("P");
-    keymap[VK_Q.0 as usize] = Some("Q");
-    keymap[VK_R.0 as usize] = Some("R");
-    keymap[VK_S.0 as usize] = Some("S");
-    keymap[VK_T.0 as usize] = Some("T");
-    keymap[VK_U.0 as usize] = Some("U");
-    keymap[VK_V.0 as usize] = Some("V");
-    keymap[VK_W.0 as usize] = Some("W");
-    keymap[VK_X.0 as usize] = Some("X");
-    keymap[VK_Y.0 as usize] = Some("Y");
-    keymap[VK_Z.0 as usize] = Some("Z");
-
-    // oem-keys
-    keymap[VK_OEM_PLUS.0 as usize] = Some("+");
-    keymap[VK_OEM_COMMA.0 as usize] = Some(",");
-    keymap[VK_OEM_MINUS.0 as usize] = Some("-");
-    keymap[VK_OEM_PERIOD.0 as usize] = Some(".");
-
-    // Functions
-    keymap[VK_F1.0 as usize] = Some("F1");
-    keymap[VK_F2.0 as usize] = Some("F2");
-    keymap[VK_F3.0 as usize] = Some("F3");
-    keymap[VK_F4.0 as usize] = Some("F4");
-    keymap[VK_F5.0 as usize] = Some("F5");
-    keymap[VK_F6.0 as usize] = Some("F6");
-    keymap[VK_F7.0 as usize] = Some("F7");
-    keymap[VK_F8.0 as usize] = Some("F8");
-    keymap[VK_F9.0 as usize] = Some("F9");
-    keymap[VK_F10.0 as usize] = Some("F10");
-    keymap[VK_F11.0 as usize] = Some("F11");
-    keymap[VK_F12.0 as usize] = Some("F12");
-    keymap[VK_F13.0 as usize] = Some("F13");
-    keymap[VK_F14.0 as usize] = Some("F14");
-    keymap[VK_F15.0 as usize] = Some("F15");
-    keymap[VK_F16.0 as usize] = Some("F16");
-    keymap[VK_F17.0 as usize] = Some("F17");
-    keymap[VK_F18.0 as usize] = Some("F18");
-    keymap[VK_F19.0 as usize] = Some("F19");
-    keymap[VK_F20.0 as usize] = Some("F20");
-    keymap[VK_F21.0 as usize] = Some("F21");
-    keymap[VK_F22.0 as usize] = Some("F22");
-    keymap[VK_F23.0 as usize] = Some("F23");
-    keymap[VK_F24.0 as usize] = Some("F24");
-    keymap
-}
-
 struct KeyboardState {
     last_scancode: u32,
     last_virtkey: u32,
     last_state: [u8; 256],
-    keymap: [Option<&'static str>; 256],
+    scancodes: [u32; 256],
+    modifiers: ModifierState,
 }
 
 impl KeyboardState {
     fn new() -> Self {
-        KeyboardState {
+        let mut modifiers = ModifierState::new();
+        for (vk, key) in [
+            (VK_CAPITAL, ModifierKey::CapsLock),
+            (VK_NUMLOCK, ModifierKey::NumLock),
+            (VK_SCROLL, ModifierKey::ScrollLock),
+        ] {
+            let toggled = unsafe { GetKeyState(vk.0 as i32) } & 0x01 != 0;
+            modifiers.set_locked(key, toggled);
+        }
+        let mut keyboard = KeyboardState {
             last_scancode: 0,
             last_virtkey: 0,
             last_state: [0u8; 256],
-            keymap: initialize_keymap(),
+            scancodes: [0u32; 256],
+            modifiers,
+        };
+        keyboard.sync_modifiers();
+        keyboard
+    }
+    fn update(&mut self, virtkey: u16, scancode: u32, keyaction: KeyAction) {
+        self.scancodes[virtkey as usize] = scancode;
+        let pressed = match keyaction {
+            KeyAction::KEYDOWN => true,
+            KeyAction::KEYUP => false,
+            KeyAction::OTHER => return,
+        };
+        match ModifierKey::from_vk(virtkey, scancode) {
+            Some(key) => {
+                self.modifiers.update(key, pressed);
+                self.sync_modifiers();
+            }
+            None if pressed => {
+                self.last_state[virtkey as usize] |= 0x80;
+                self.last_virtkey = virtkey as u32;
+                self.last_scancode = scancode;
+            }
+            None => {
+                self.last_state[virtkey as usize] &= !0x80;
+            }
         }
     }
-    fn update(&mut self, virtkey: u16, keyaction: KeyAction) {
-        match keyaction {
-            KeyAction::KEYDOWN => match VIRTUAL_KEY(virtkey) {
-                VK_SHIFT => {
-                    self.last_state[VK_SHIFT.0 as usize] |= 0x80;
-                    self.last_state[VK_LSHIFT.0 as usize] |= 0x80;
-                    self.last_state[VK_RSHIFT.0 as usize] |= 0x80;
-                }
-                VK_CONTROL => {
-                    self.last_state[VK_CONTROL.0 as usize] |= 0x80;
-                    self.last_state[VK_LCONTROL.0 as usize] |= 0x80;
-                    self.last_state[VK_RCONTROL.0 as usize] |= 0x80;
-                }
-                VK_MENU => {
-                    self.last_state[VK_MENU.0 as usize] |= 0x80;
-                    self.last_state[VK_LMENU.0 as usize] |= 0x80;
-                    self.last_state[VK_RMENU.0 as usize] |= 0x80;
-                }
-                VK_CAPITAL => {
-                    self.last_state[VK_CAPITAL.0 as usize] ^= 0x01;
-                }
-                _ => {
-                    self.last_state[virtkey as usize] |= 0x80;
-                    self.last_virtkey = virtkey as u32;
-                }
-            },
-            KeyAction::KEYUP => match VIRTUAL_KEY(virtkey) {
-                VK_SHIFT => {
-                    self.last_state[VK_SHIFT.0 as usize] &= !0x80;
-                    self.last_state[VK_LSHIFT.0 as usize] &= !0x80;
-                }
-                VK_CONTROL => {
-                    self.last_state[VK_CONTROL.0 as usize] &= !0x80;
-                    self.last_state[VK_LCONTROL.0 as usize] &= !0x80;
-                    self.last_state[VK_RCONTROL.0 as usize] &= !0x80;
-                }
-                VK_MENU => {
-                    self.last_state[VK_MENU.0 as usize] &= !0x80;
-                    self.last_state[VK_LMENU.0 as usize] &= !0x80;
-                    self.last_state[VK_RMENU.0 as usize] &= !0x80;
-                }
-                _ => {
-                    self.last_state[virtkey as usize] &= !0x80;
-                }
-            },
-            KeyAction::OTHER => {}
+    fn set_state(&mut self, virtkey: VIRTUAL_KEY, pressed: bool, toggled: bool) {
+        let mut state = if toggled { 0x01 } else { 0x00 };
+        if pressed {
+            state |= 0x80;
+        }
+        self.last_state[virtkey.0 as usize] = state;
+    }
+    // Mirrors the modifier state into the key state array handed to ToUnicodeEx.
+    fn sync_modifiers(&mut self) {
+        let m = self.modifiers.clone();
+        let lshift = m.is_pressed(ModifierKey::LeftShift);
+        let rshift = m.is_pressed(ModifierKey::RightShift);
+        let lctrl = m.is_pressed(ModifierKey::LeftCtrl) || m.is_pressed(ModifierKey::AltGr);
+        let rctrl = m.is_pressed(ModifierKey::RightCtrl);
+        let lalt = m.is_pressed(ModifierKey::LeftAlt);
+        let ralt = m.is_pressed(ModifierKey::RightAlt);
+        self.set_state(VK_LSHIFT, lshift, false);
+        self.set_state(VK_RSHIFT, rshift, false);
+        self.set_state(VK_SHIFT, lshift || rshift, false);
+        self.set_state(VK_LCONTROL, lctrl, false);
+        self.set_state(VK_RCONTROL, rctrl, false);
+        self.set_state(VK_CONTROL, lctrl || rctrl, false);
+        self.set_state(VK_LMENU, lalt, false);
+        self.set_state(VK_RMENU, ralt, false);
+        self.set_state(VK_MENU, lalt || ralt, false);
+        self.set_state(VK_LWIN, m.is_pressed(ModifierKey::LeftSuper), false);
+        self.set_state(VK_RWIN, m.is_pressed(ModifierKey::RightSuper), false);
+        for (vk, key) in [
+            (VK_CAPITAL, ModifierKey::CapsLock),
+            (VK_NUMLOCK, ModifierKey::NumLock),
+            (VK_SCROLL, ModifierKey::ScrollLock),
+        ] {
+            self.set_state(vk, m.is_pressed(key), m.is_locked(key));
+        }
+    }
+    fn is_pressed(&self, virtkey: VIRTUAL_KEY) -> bool {
+        self.last_state[virtkey.0 as usize] & 0x80 != 0
+    }
+    // Resolves the character of a printable key in the layout of the foreground window.
+    fn translate(&self, virtkey: u16) -> Option<String> {
+        if !vkcode::is_printable(virtkey) {
+            return None;
+        }
+        let mut state = self.last_state;
+        // Ctrl alone turns letters into control characters, Ctrl+Alt is AltGr.
+        if !self.is_pressed(VK_MENU) {
+            state[VK_CONTROL.0 as usize] = 0;
+            state[VK_LCONTROL.0 as usize] = 0;
+            state[VK_RCONTROL.0 as usize] = 0;
+        }
+        let mut buf = [0u16; 8];
+        // 0x4: do not change the keyboard state, keeps pending dead keys intact
+        let len = unsafe {
+            let thread = GetWindowThreadProcessId(GetForegroundWindow(), None);
+            ToUnicodeEx(
+                virtkey as u32,
+                self.scancodes[virtkey as usize],
+                &state,
+                &mut buf,
+                0x4,
+                GetKeyboardLayout(thread),
+            )
+        };
+        // negative length means a dead key, the buffer holds its spacing form
+        let len = (len.unsigned_abs() as usize).min(buf.len());
+        let translated = String::from_utf16_lossy(&buf[..len]);
+        if !translated.is_empty() && !translated.chars().any(char::is_control) {
+            return Some(translated);
         }
+        vkcode::translate_us(
+            virtkey,
+            self.is_pressed(VK_SHIFT),
+            self.modifiers.is_locked(ModifierKey::CapsLock),
+        )
+        .map(String::from)
     }
-    fn get_syms(&self) -> Vec<&'static str> {
-        let mut syms = Vec::new();
+    fn get_syms(&self, symbols: &SymbolMap, sides: bool) -> Vec<keyway::Key> {
+        let mut syms = self.modifiers.modifiers().keys(sides);
         for (i, state ) in self.last_state.iter().enumerate() {
-            if *state & 0x80 != 0 {
-                match self.keymap[i] {
-                    Some(sym) => {
-                        syms.push(sym);
-                    }
+            // held modifiers come from the labels above, lock keys show as keys
+            let modifier = ModifierKey::from_vk(i as u16, 0).is_some_and(|k| !k.is_lock());
+            if *state & 0x80 != 0 && !modifier {
+                let class = vkcode::class(i as u16);
+                match vkcode::name(i as u16) {
+                    Some(sym) => match symbols.lookup(sym, self.scancodes[i]) {
+                        Some(label) => syms.push(keyway::Key::labeled(label, class)),
+                        None => syms.push(keyway::Key::new(
+                            self.translate(i as u16).unwrap_or_else(|| sym.to_string()), class,
+                        )),
+                    },
                     None => {}
                 }
             }
@@ -289,60 +251,137 @@ impl KeyboardState {
     }
 }
 
-pub fn run_sender(timeout: Arc<RwLock<u32>>, apphandle: AppHandle, label: String, event: String) {
-    init_channel();
-    let hook = std::thread::spawn(|| {
-        keyboad_hook();
-    });
-    let recv = std::thread::spawn(move || {
-        let mut keystrokes = Vec::<Vec<&str>>::new();
-        let mut timestamp = Instant::now();
+/// Reads key events from a low level keyboard hook.
+pub struct HookBackend {
+    held: [bool; 256],
+    start: Instant,
+}
+
+impl HookBackend {
+    pub fn open() -> Result<Self> {
+        init_channel();
+        let mut hook = HOOK.lock().map_err(|e| io::Error::other(e.to_string()))?;
+        if hook.as_ref().is_none_or(|h| h.is_finished()) {
+            *hook = Some(std::thread::spawn(|| {
+                if let Err(e) = keyboad_hook() {
+                    error!("Keyboard hook failed: {e}");
+                }
+            }));
+        }
+        Ok(HookBackend {
+            held: [false; 256],
+            start: Instant::now(),
+        })
+    }
+    fn key_event(&mut self, key: Key) -> Option<KeyEvent> {
+        let held = &mut self.held[key.virtkey as usize & 0xff];
+        let state = match key.keyaction {
+            KeyAction::KEYUP => Some(KeyState::Release),
+            KeyAction::KEYDOWN if *held => Some(KeyState::Repeat),
+            KeyAction::KEYDOWN => Some(KeyState::Press),
+            KeyAction::OTHER => None,
+        }?;
+        *held = state != KeyState::Release;
+        Some(KeyEvent {
+            time: self.start.elapsed().as_millis() as u64,
+            device: 0,
+            scancode: key.scancode,
+            virtkey: key.virtkey,
+            state,
+        })
+    }
+}
+
+impl InputBackend for HookBackend {
+    fn devices(&self) -> Vec<DeviceInfo> {
+        let mut klid = [0u16; 9];
+        let keymap = unsafe { GetKeyboardLayoutNameW(&mut klid) }
+            .ok()
+            .map(|_| String::from_utf16_lossy(&klid[..8]));
+        let locks = [
+            (VK_CAPITAL, ModifierKey::CapsLock),
+            (VK_NUMLOCK, ModifierKey::NumLock),
+            (VK_SCROLL, ModifierKey::ScrollLock),
+        ]
+        .into_iter()
+        .filter(|(vk, _)| unsafe { GetKeyState(vk.0 as i32) } & 0x01 != 0)
+        .map(|(_, key)| key)
+        .collect();
+        vec![DeviceInfo {
+            id: 0,
+            name: "keyboard hook".to_string(),
+            keymap,
+            locks,
+        }]
+    }
+    fn poll(&mut self, timeout: Duration) -> Result<Vec<KeyEvent>> {
+        let hook_alive = HOOK
+            .lock()
+            .is_ok_and(|hook| hook.as_ref().is_some_and(|h| !h.is_finished()));
+        if !hook_alive {
+            return Err(Error::Io(io::Error::other("keyboard hook stopped")));
+        }
+        let rx = RX
+            .get()
+            .ok_or_else(|| io::Error::other("keyboard hook not started"))?
+            .lock()
+            .map_err(|e| io::Error::other(e.to_string()))?;
+        let mut keys = Vec::new();
+        if let Ok(key) = rx.recv_timeout(timeout) {
+            keys.push(key);
+            keys.extend(rx.try_iter());
+        }
+        drop(rx);
+        Ok(keys.into_iter().filter_map(|key| self.key_event(key)).collect())
+    }
+}
+
+/// The keyboard state of the hook, turning key events into display groups.
+pub struct Keyboards {
+    keyboard: KeyboardState,
+}
+
+impl Keyboards {
+    pub fn new(devices: &[DeviceInfo]) -> Result<Self> {
         let mut keyboard = KeyboardState::new();
-        '_keysend_loop: loop {
-            let timeout = Duration::from_millis(*timeout.read().unwrap() as u64);
-            match RX
-                .get()
-                .expect("Failed get")
-                .lock()
-                .expect("Failed read")
-                .recv_timeout(Duration::from_millis(50))
-            {
-                Ok(recv) => match recv.keyaction {
-                    KeyAction::KEYDOWN => {
-                        timestamp = Instant::now();
-                        keyboard.update(recv.virtkey as u16, recv.keyaction);
-                        let keysyms = keyboard.get_syms();
-                        debug!("{:?}", keysyms);
-                        if !keysyms.is_empty() {
-                            keystrokes.push(keysyms);
-                        }
-                    }
-                    KeyAction::KEYUP => {
-                        keyboard.update(recv.virtkey as u16, recv.keyaction);
-                    }
-                    KeyAction::OTHER => {}
-                },
-                Err(_err) => {}
+        if let Some(device) = devices.first() {
+            for key in [ModifierKey::CapsLock, ModifierKey::NumLock, ModifierKey::ScrollLock] {
+                keyboard.modifiers.set_locked(key, device.locks.contains(&key));
             }
-            let now = Instant::now();
-            if !keystrokes.is_empty() && (now - timestamp > timeout) {
-                keystrokes.clear();
-                debug!("Key Cleared");
+            keyboard.sync_modifiers();
+        }
+        Ok(Keyboards { keyboard })
+    }
+    /// Applies `event` and returns the group to display for it, if any.
+    pub fn handle(&mut self, event: &KeyEvent, symbols: &SymbolMap, sides: bool) -> Option<Vec<keyway::Key>> {
+        // virtual-key codes fit in 8 bits, anything else did not come from the hook
+        let Ok(virtkey) = u8::try_from(event.virtkey) else {
+            warn!("Ignoring virtual-key code {:#x} above 0xff", event.virtkey);
+            return None;
+        };
+        let virtkey = u16::from(virtkey);
+        match event.state {
+            KeyState::Release => {
+                self.keyboard.update(virtkey, event.scancode, KeyAction::KEYUP);
+                None
             }
-            if !keystrokes.is_empty() {
-                debug!("Keystrokes: {:#?}", keystrokes);
+            KeyState::Press | KeyState::Repeat => {
+                self.keyboard.update(virtkey, event.scancode, KeyAction::KEYDOWN);
+                Some(self.keyboard.get_syms(symbols, sides))
             }
-            apphandle
-                .emit_to(&label, &event, keystrokes.clone())
-                .unwrap();
-            // keystrokes.clear();
         }
-    });
-    recv.join().expect("Failed join recv");
-    hook.join().expect("Failed join hook");
+    }
+    /// The modifiers and lock toggles of the keyboard.
+    pub fn modifiers(&self, _device: u32) -> Modifiers {
+        self.keyboard.modifiers.modifiers()
+    }
+    /// Releases a key whose release event never arrived.
+    pub fn release(&mut self, _device: u32, virtkey: u32) {
+        let scancode = self.keyboard.scancodes[virtkey as usize & 0xff];
+        self.keyboard.update(virtkey as u16, scancode, KeyAction::KEYUP);
+    }
 }
 
-
 #[cfg(test)]
 mod test {
     use super::*;
@@ -353,4 +392,22 @@ mod test {
             println!("")
         }
     }
+
+    #[test]
+    fn test_foreign_codes() {
+        let mut keyboards = Keyboards::new(&[]).unwrap();
+        let event = KeyEvent {
+            time: 0,
+            device: 0,
+            scancode: 0x1e,
+            virtkey: 0x141,
+            state: KeyState::Press,
+        };
+        assert_eq!(keyboards.handle(&event, &SymbolMap::new(), false), None);
+        let release = KeyEvent {
+            state: KeyState::Release,
+            ..event
+        };
+        assert_eq!(keyboards.handle(&release, &SymbolMap::new(), false), None);
+    }
 }
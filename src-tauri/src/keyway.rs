@@ -1,24 +1,78 @@
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Keystroke {
-    virtkey: u32,
-    symbol: String, 
+use crate::symbol::Label;
+
+/// What kind of key a label stands for, so each kind can be styled apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyClass {
+    /// Shift, Ctrl, Alt, Super and the lock keys.
+    Modifier,
+    /// Arrows, Home, End and Page Up/Down.
+    Navigation,
+    /// F1-F24, Escape, Print Screen and other command keys.
+    Function,
+    /// Enter, Tab, Backspace, Delete and Insert.
+    Editing,
+    /// Keys that type a character, Space included.
+    Printable,
+    /// Mouse buttons.
+    Mouse,
+}
+
+/// A key as displayed: its label, class and the icon from the labels file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Key {
+    pub label: String,
+    pub class: KeyClass,
+    /// Absolute path of an image shown before the label.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
 }
 
-impl Keystroke {
-    pub fn new(virtkey: u32, symbol: String) -> Self {
-        Keystroke {
-            virtkey,
-            symbol,
+impl Key {
+    pub fn new(label: impl Into<String>, class: KeyClass) -> Self {
+        Key {
+            label: label.into(),
+            class,
+            icon: None,
+        }
+    }
+    /// A key relabeled by the labels file.
+    pub fn labeled(label: &Label, class: KeyClass) -> Self {
+        Key {
+            label: label.text().to_string(),
+            class,
+            icon: label.icon().map(str::to_string),
         }
     }
 }
 
-impl fmt::Display for Keystroke {
+impl fmt::Display for Key {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Key[{:#x} '{}']", self.virtkey, self.symbol)
+        f.write_str(&self.label)
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyState {
+    Release,
+    Press,
+    Repeat,
+}
+
+/// A raw key event as read from the device, before any keymap is applied.
+///
+/// `scancode` is the evdev key code on Linux and the hardware scan code on
+/// Windows, `virtkey` the xkb keycode or the virtual-key code. `time` counts
+/// milliseconds from the start of the session.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeyEvent {
+    pub time: u64,
+    pub device: u32,
+    pub scancode: u32,
+    pub virtkey: u32,
+    pub state: KeyState,
+}
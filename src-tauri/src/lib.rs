@@ -0,0 +1,35 @@
+//! Keystroke capture and aggregation behind the keyway overlay.
+//!
+//! Key events come from an [`keysender::InputBackend`] (the keyboards of this
+//! machine or a recorded session), are turned into labels through the active
+//! keymap and [`symbol::SymbolMap`], grouped by the [`aggregator::Aggregator`]
+//! and handed to a [`sink::Sink`]. [`keysender::run_pipeline`] ties these
+//! together.
+//!
+//! Features:
+//! - `tauri-ui` (default): the sink that emits to the keyway window and
+//!   [`desktop`].
+//! - `evdev` (default): reading keyboards under /dev/input on Linux and
+//!   translating key events with xkbcommon, which [`keysender`] needs there.
+pub mod aggregator;
+pub mod config;
+#[cfg(feature = "tauri-ui")]
+pub mod desktop;
+pub mod error;
+pub mod geometry;
+#[cfg(any(not(target_os = "linux"), feature = "evdev"))]
+pub mod keysender;
+pub mod keyway;
+pub mod measure;
+pub mod modifier;
+pub mod recorder;
+pub mod sink;
+pub mod state;
+pub mod symbol;
+pub mod theme;
+pub mod vkcode;
+
+#[cfg(test)]
+mod testutil;
+
+pub use keyway::{Key, KeyClass, KeyEvent, KeyState};
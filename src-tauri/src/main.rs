@@ -1,131 +1,270 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-mod keysender;
-mod keyway;
-use keysender::run_sender;
+mod commands;
+mod overlay;
 
+use keyway::config::{self, Config, ConfigPatch};
+use keyway::error::Error;
+use keyway::keysender::{run_sender, InputBackend, NativeBackend, ReplayBackend};
+use keyway::measure::Measurer;
+use keyway::recorder::{Recorder, Recording};
+use keyway::sink::{Fanout, LineSink, TauriSink, TcpSink};
+use keyway::state::AppState;
+use keyway::symbol::{self, SymbolMap};
+use keyway::theme::{self, Theme, Themes};
+use keyway::Key;
+
+use clap::Parser;
+use std::fmt;
+use std::path::PathBuf;
 use std::sync::{Arc, RwLock};
-use log::debug;
+use std::time::Duration;
+use tokio::sync::watch;
+use log::{debug, warn};
 use std::env;
 use tauri::{
-    CustomMenuItem, Manager, SystemTray, SystemTrayEvent, SystemTrayMenu, SystemTrayMenuItem,
+    AppHandle, CustomMenuItem, Manager, SystemTray, SystemTrayEvent, SystemTrayMenu,
+    SystemTrayMenuItem,
 };
 
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    /// Record the key events of this session to FILE
+    #[arg(long, value_name = "FILE")]
+    record: Option<PathBuf>,
+    /// Play back a recorded session instead of reading the keyboard
+    #[arg(long, value_name = "FILE")]
+    replay: Option<PathBuf>,
+    /// Playback speed of --replay, 2.0 plays twice as fast, at least 0.01
+    #[arg(long, default_value_t = 1.0)]
+    speed: f64,
+    /// Also print the displayed keystrokes to stdout as JSON lines
+    #[arg(long)]
+    stdout: bool,
+    /// Also write the displayed keystrokes to FILE as JSON lines
+    #[arg(long, value_name = "FILE")]
+    output: Option<PathBuf>,
+    /// Also serve the displayed keystrokes as JSON lines over TCP on ADDR
+    #[arg(long, value_name = "ADDR")]
+    listen: Option<String>,
+}
+
+/// Sends the parts of the config the key window renders whenever they change,
+/// and the whole config to the config window, which may not have made the
+/// change itself.
+async fn forward_config(app: AppHandle, mut config: watch::Receiver<Config>) {
+    let mut current = config.borrow_and_update().clone();
+    while config.changed().await.is_ok() {
+        let changed = config.borrow_and_update().clone();
+        app.emit_to("ConfigWindow", "on-change-config", &changed)
+            .unwrap_or_else(|e| warn!("Failed to emit on-change-config: {e}"));
+        if changed.typography != current.typography {
+            app.emit_to("KeyWindow", "on-change-typography", &changed.typography)
+                .unwrap_or_else(|e| warn!("Failed to emit on-change-typography: {e}"));
+        }
+        if changed.windowappearance != current.windowappearance {
+            app.emit_to(
+                "KeyWindow",
+                "on-change-windowappearance",
+                &changed.windowappearance,
+            )
+            .unwrap_or_else(|e| warn!("Failed to emit on-change-windowappearance: {e}"));
+        }
+        current = changed;
+    }
+}
+
+/// How often the themes directory is checked for changed files.
+const THEME_INTERVAL: Duration = Duration::from_secs(1);
 
-#[derive(Clone, serde::Serialize, serde::Deserialize)]
-struct BehaviorParameter {
-    timeout: u32,
-    mousevisible: bool,
-    modvisible: bool,
+/// Sends the selected theme to the key window whenever another one is chosen
+/// or its file changes, and the theme names to the config window when the
+/// themes directory changes.
+async fn forward_theme(
+    app: AppHandle,
+    themes: Arc<RwLock<Themes>>,
+    sizer: Arc<overlay::Sizer>,
+    mut config: watch::Receiver<Config>,
+) {
+    let selected = |config: &mut watch::Receiver<Config>| -> Theme {
+        let name = config.borrow_and_update().windowappearance.theme.clone();
+        themes.read().unwrap().get(&name)
+    };
+    let mut current = selected(&mut config);
+    loop {
+        if let Ok(changed) = tokio::time::timeout(THEME_INTERVAL, config.changed()).await {
+            if changed.is_err() {
+                return;
+            }
+        }
+        let reloaded = themes.write().unwrap().reload_if_changed();
+        if reloaded {
+            let names = themes.read().unwrap().names();
+            app.emit_to("ConfigWindow", "on-change-themes", names)
+                .unwrap_or_else(|e| warn!("Failed to emit on-change-themes: {e}"));
+        }
+        let theme = selected(&mut config);
+        if theme != current {
+            app.emit_to("KeyWindow", "on-change-theme", &theme)
+                .unwrap_or_else(|e| warn!("Failed to emit on-change-theme: {e}"));
+            sizer.resize();
+            current = theme;
+        }
+    }
 }
 
-#[derive(Clone, serde::Serialize, serde::Deserialize)]
-struct TypographyParameter {
-    fontsize: u32,
-    fontfamily: String,
-    textcolor: String,
+/// How long changes are collected before the config is saved, so dragging the
+/// key window does not write it on every step.
+const SAVE_DELAY: Duration = Duration::from_millis(500);
+
+/// Writes the config to `path` after every change.
+async fn save_config(path: PathBuf, mut config: watch::Receiver<Config>) {
+    while config.changed().await.is_ok() {
+        tokio::time::sleep(SAVE_DELAY).await;
+        let changed = config.borrow_and_update().clone();
+        changed
+            .save(&path)
+            .unwrap_or_else(|e| warn!("Failed to save config {:?}: {e}", path));
+    }
 }
 
-#[derive(Clone, serde::Serialize, serde::Deserialize)]
-struct WindowAppearanceParameter {
-    backgroundcolor: String,
-    transparantetoggle: bool,
-    backgroundopacity: f32,
+/// Reports a file or address given on the command line that cannot be used
+/// and exits.
+fn exit_with(what: &str, target: impl fmt::Display, e: impl fmt::Display) -> ! {
+    eprintln!("keyway: {what} {target}: {e}");
+    std::process::exit(1);
 }
 
 fn main() {
+    let cli = Cli::parse();
     env::set_var("RUST_LOG", "DEBUG");
     env_logger::init();
     debug!("Starting keyway");
+    let recorder = cli.record.map(|path| {
+        debug!("Recording to {:?}", path);
+        Recorder::create(&path)
+            .unwrap_or_else(|e| exit_with("cannot create recording", path.display(), e))
+    });
+    let replay = cli.replay.map(|path| {
+        debug!("Replaying {:?}", path);
+        let recording = Recording::open(&path)
+            .unwrap_or_else(|e| exit_with("cannot read recording", path.display(), e));
+        ReplayBackend::new(recording, cli.speed)
+            .unwrap_or_else(|e| exit_with("cannot replay", path.display(), e))
+    });
+    let mut sinks = Fanout::new();
+    if cli.stdout {
+        sinks.push(Box::new(LineSink::stdout()));
+    }
+    if let Some(path) = cli.output {
+        let sink = LineSink::create(&path)
+            .unwrap_or_else(|e| exit_with("cannot create output", path.display(), e));
+        sinks.push(Box::new(sink));
+    }
+    if let Some(addr) = cli.listen {
+        let sink = TcpSink::bind(&addr).unwrap_or_else(|e| exit_with("cannot listen on", &addr, e));
+        debug!("Serving keystrokes on {:?}", sink.local_addr());
+        sinks.push(Box::new(sink));
+    }
     let quit = CustomMenuItem::new("quit".to_string(), "Quit");
     let open = CustomMenuItem::new("open".to_string(), "Open");
     let hide = CustomMenuItem::new("hide".to_string(), "Hide");
+    let click_through =
+        CustomMenuItem::new(overlay::CLICK_THROUGH_ITEM.to_string(), "Click-through");
+    let move_mode = CustomMenuItem::new(overlay::MOVE_ITEM.to_string(), "Move key window");
     let tray_menu = SystemTrayMenu::new()
         .add_item(quit)
         .add_native_item(SystemTrayMenuItem::Separator)
         .add_item(open)
         .add_native_item(SystemTrayMenuItem::Separator)
-        .add_item(hide);
+        .add_item(hide)
+        .add_native_item(SystemTrayMenuItem::Separator)
+        .add_item(click_through)
+        .add_item(move_mode);
     let system_tray = SystemTray::new().with_menu(tray_menu);
     tauri::Builder::default()
-        .setup(|app| {
-            let config_window = app.get_window("ConfigWindow").unwrap();
+        .setup(move |app| {
             let key_window = app.get_window("KeyWindow").unwrap();
             debug!("InnerPosition:{:?}", key_window.inner_position());
             debug!("OuterPosition:{:?}", key_window.outer_position());
             debug!("InnerSize:{:?}", key_window.inner_size());
             debug!("OuterSize:{:?}", key_window.outer_size());
-            let timeout = Arc::new(RwLock::new(500u32));
-            // ************** Behavior *****************
-            {
-                let timeout_ = timeout.clone();
-                config_window.listen("on-change-behavior", move |event| {
-                    debug!(
-                        "ConfigWindow onChangeBehavior: {:?}",
-                        event.payload()
-                    );
-                    let behavior_param =
-                        serde_json::from_str::<BehaviorParameter>(event.payload().unwrap())
-                            .unwrap();
-                    *timeout_.write().unwrap() = behavior_param.timeout;
-                });
-                debug!("Setup on-change-behavior");
-            }
-
-            // ************** Typography *****************
-            {
-                let key_window_ = key_window.clone();
-                config_window.listen("on-change-typography", move |event| {
-                   debug!(
-                        "ConfigWindow onChangeTypography: {:?}",
-                        event.payload()
-                    );
-                    key_window_
-                        .emit(
-                            "on-change-typography",
-                            serde_json::from_str::<TypographyParameter>(event.payload().unwrap())
-                                .unwrap(),
-                        )
-                        .unwrap();
-                });
-                debug!("Setup on-change-typography");
-            }
-
-            // ************** WindowAppearance *****************
-            {
-                let key_window_ = key_window.clone();
-                config_window.listen("on-change-windowappearance", move |event| {
-                    debug!(
-                        "ConfigWindow onChangeWindowAppearance: {:?}",
-                        event.payload()
-                    );
-                    key_window_
-                        .emit(
-                            "on-change-windowappearance",
-                            serde_json::from_str::<WindowAppearanceParameter>(
-                                event.payload().unwrap(),
-                            )
-                            .unwrap(),
-                        )
-                        .unwrap();
-                });
-                debug!("Setup on-change-windowappearance");
+            let symbols = match app.path_resolver().app_config_dir() {
+                Some(dir) => SymbolMap::load(&dir.join(symbol::LABELS_FILE)),
+                None => SymbolMap::new(),
+            };
+            let symbols = Arc::new(RwLock::new(symbols));
+            symbol::watch(symbols.clone(), Duration::from_secs(1));
+            // ************** Settings *****************
+            let measurer = Arc::new(Measurer::system());
+            let fonts = measurer.families();
+            let themes = match app.path_resolver().app_config_dir() {
+                Some(dir) => Themes::load(&dir.join(theme::THEMES_DIR)),
+                None => Themes::new(),
+            };
+            let themes = Arc::new(RwLock::new(themes));
+            let config_path = app
+                .path_resolver()
+                .app_config_dir()
+                .map(|dir| dir.join(config::CONFIG_FILE));
+            let config = match config_path.as_ref() {
+                Some(path) => Config::load(path, &fonts),
+                None => Config::default(),
+            };
+            let state = AppState::new(config, fonts, themes.clone());
+            if let Some(path) = config_path {
+                tauri::async_runtime::spawn(save_config(path, state.subscribe()));
             }
+            let config = state.subscribe();
+            tauri::async_runtime::spawn(forward_config(app.app_handle(), state.subscribe()));
+            let sizer = Arc::new(overlay::Sizer::new(
+                key_window.clone(),
+                state.subscribe(),
+                measurer,
+                themes.clone(),
+            ));
+            tauri::async_runtime::spawn(forward_theme(
+                app.app_handle(),
+                themes.clone(),
+                sizer.clone(),
+                state.subscribe(),
+            ));
+            app.manage(themes);
+            overlay::start(key_window.clone(), state.subscribe(), sizer.clone());
+            let presence = Arc::new(overlay::Presence::new(key_window.clone()));
+            tauri::async_runtime::spawn(overlay::follow_behavior(
+                presence.clone(),
+                state.subscribe(),
+            ));
+            app.manage(presence.clone());
+            app.manage(state);
 
             // ************** KeySender *****************
-            let apphandle = app.app_handle();
-            tauri::async_runtime::spawn(async move {
-                run_sender(
-                    timeout.clone(),
-                    apphandle,
-                    "KeyWindow".to_string(),
-                    "keyevent".to_string(),
-                );
-            });
+            sinks.push(Box::new(TauriSink::new(app.app_handle(), "KeyWindow", "keyevent")));
+            sinks.push(Box::new(move |groups: &[Vec<Key>]| sizer.show(groups)));
+            sinks.push(Box::new(move |groups: &[Vec<Key>]| presence.show(groups)));
+            let mut replay = replay;
+            let open = move || -> Result<Box<dyn InputBackend>, Error> {
+                match replay.take() {
+                    Some(backend) => Ok(Box::new(backend)),
+                    None => Ok(Box::new(NativeBackend::open()?)),
+                }
+            };
+            // the sender thread runs for as long as the app
+            run_sender(open, config, symbols, recorder, Box::new(sinks));
             debug!("Starting keysender");
             Ok(())
         })
+        .invoke_handler(tauri::generate_handler![
+            commands::get_config,
+            commands::set_config,
+            commands::list_fonts,
+            commands::list_themes,
+            commands::get_theme,
+            commands::list_monitors
+        ])
         .system_tray(system_tray)
         .on_system_tray_event(|app, event| match event {
             SystemTrayEvent::MenuItemClick { tray_id, id, .. } => match id.as_str() {
@@ -146,6 +285,22 @@ fn main() {
                         Err(e) => eprintln!("{e}"),
                     }
                 }
+                overlay::CLICK_THROUGH_ITEM => {
+                    let state = app.state::<AppState>();
+                    let mut behavior = state.config().behavior;
+                    behavior.clickthrough = !behavior.clickthrough;
+                    let patch = ConfigPatch {
+                        behavior: Some(behavior),
+                        ..Default::default()
+                    };
+                    if let Err(e) = state.set_config(patch) {
+                        warn!("Failed to toggle click-through: {e}");
+                    }
+                }
+                overlay::MOVE_ITEM => {
+                    let moving = app.state::<Arc<overlay::Presence>>().toggle_moving();
+                    debug!("Move mode: {moving}");
+                }
                 _ => {}
             },
             SystemTrayEvent::LeftClick {
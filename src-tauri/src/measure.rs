@@ -0,0 +1,259 @@
+//! Sizing of the key window from the width of its labels in the chosen font.
+//!
+//! The box model mirrors the classes used by `src/KeyWindow/index.tsx`; all
+//! lengths are CSS, i.e. logical, pixels.
+use fontdb::{Database, Family, Query, ID};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::config::{FontFamilies, Layout, TypographyParameter};
+use crate::keyway::Key;
+
+/// `p-1` around the window content.
+const WINDOW_PADDING: f64 = 4.0;
+/// `h-1` grab bar followed by a `gap-1`.
+const GRABBAR: f64 = 4.0 + 4.0;
+/// `min-w-20 min-h-8` of the key row.
+const ROW_MIN_WIDTH: f64 = 80.0;
+const ROW_MIN_HEIGHT: f64 = 32.0;
+/// `p-0.5` around each group, `gap-0.5` between its keys.
+const GROUP_PADDING: f64 = 2.0;
+const KEY_GAP: f64 = 2.0;
+/// `min-w-8` of a key, including its padding and border.
+const KEY_MIN_WIDTH: f64 = 32.0;
+/// The line height of the tailwind base styles.
+const LINE_HEIGHT: f64 = 1.5;
+/// `gap-1` between an icon, drawn a line high and wide, and the label.
+const ICON_GAP: f64 = 4.0;
+/// Advance of a character no font has a glyph for, in em.
+const FALLBACK_ADVANCE: f32 = 0.6;
+
+/// The padding and the total horizontal and vertical border around a label
+/// in logical pixels, set by the theme.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeyBox {
+    pub padding: f64,
+    pub border_x: f64,
+    pub border_y: f64,
+}
+
+impl Default for KeyBox {
+    fn default() -> Self {
+        KeyBox {
+            padding: 2.0,
+            border_x: 0.0,
+            border_y: 0.0,
+        }
+    }
+}
+
+/// Measures text with the fonts installed on this machine.
+pub struct Measurer {
+    db: Database,
+    faces: Mutex<HashMap<String, Option<ID>>>,
+    // advances in em, per face and character
+    advances: Mutex<HashMap<(ID, char), f32>>,
+}
+
+impl Measurer {
+    pub fn new(db: Database) -> Self {
+        Measurer {
+            db,
+            faces: Mutex::new(HashMap::new()),
+            advances: Mutex::new(HashMap::new()),
+        }
+    }
+    pub fn system() -> Self {
+        let mut db = Database::new();
+        db.load_system_fonts();
+        Measurer::new(db)
+    }
+    pub fn families(&self) -> FontFamilies {
+        self.db
+            .faces()
+            .flat_map(|face| {
+                face.families
+                    .iter()
+                    .map(|(name, _)| (name.clone(), face.monospaced))
+            })
+            .collect()
+    }
+    /// The face used for `family`, falling back to sans-serif and then any
+    /// installed face.
+    fn face(&self, family: &str) -> Option<ID> {
+        let mut faces = self.faces.lock().unwrap();
+        *faces.entry(family.to_string()).or_insert_with(|| {
+            let family = match family {
+                "SansSerif" | "sans-serif" => Family::SansSerif,
+                "Serif" | "serif" => Family::Serif,
+                "Monospace" | "monospace" => Family::Monospace,
+                "cursive" => Family::Cursive,
+                "fantasy" => Family::Fantasy,
+                name => Family::Name(name),
+            };
+            let query = Query {
+                families: &[family, Family::SansSerif],
+                ..Query::default()
+            };
+            self.db
+                .query(&query)
+                .or_else(|| self.db.faces().next().map(|face| face.id))
+        })
+    }
+    /// The width of `text` set in `family` at `size` pixels.
+    pub fn text_width(&self, family: &str, size: f32, text: &str) -> f32 {
+        let Some(id) = self.face(family) else {
+            return text.chars().count() as f32 * FALLBACK_ADVANCE * size;
+        };
+        let mut advances = self.advances.lock().unwrap();
+        let missing = text
+            .chars()
+            .filter(|c| !advances.contains_key(&(id, *c)))
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            self.db.with_face_data(id, |data, index| {
+                let Ok(face) = ttf_parser::Face::parse(data, index) else {
+                    return;
+                };
+                let em = face.units_per_em() as f32;
+                for c in missing.iter() {
+                    let advance = face
+                        .glyph_index(*c)
+                        .and_then(|glyph| face.glyph_hor_advance(glyph))
+                        .map_or(FALLBACK_ADVANCE, |advance| advance as f32 / em);
+                    advances.insert((id, *c), advance);
+                }
+            });
+        }
+        text.chars()
+            .map(|c| advances.get(&(id, c)).copied().unwrap_or(FALLBACK_ADVANCE))
+            .sum::<f32>()
+            * size
+    }
+}
+
+/// The logical size of the key window showing `groups` in `layout` with
+/// keys drawn as `key`, given the width of a label.
+pub fn window_size<F: Fn(&str) -> f32>(
+    groups: &[Vec<Key>],
+    fontsize: u32,
+    layout: Layout,
+    key: KeyBox,
+    text_width: F,
+) -> (f64, f64) {
+    let icon_width = |k: &Key| match (&k.icon, k.label.is_empty()) {
+        (None, _) => 0.0,
+        (Some(_), true) => fontsize as f64 * LINE_HEIGHT,
+        (Some(_), false) => fontsize as f64 * LINE_HEIGHT + ICON_GAP,
+    };
+    let group_widths = groups.iter().map(|keys| {
+        keys.iter()
+            .map(|k| {
+                let content = text_width(&k.label) as f64 + icon_width(k);
+                (content + 2.0 * key.padding + key.border_x).max(KEY_MIN_WIDTH)
+            })
+            .sum::<f64>()
+            + keys.len().saturating_sub(1) as f64 * KEY_GAP
+            + 2.0 * GROUP_PADDING
+    });
+    let group_height =
+        fontsize as f64 * LINE_HEIGHT + 2.0 * key.padding + key.border_y + 2.0 * GROUP_PADDING;
+    let (row_width, row_height) = if layout.is_vertical() {
+        (
+            group_widths.fold(0.0, f64::max),
+            groups.len().max(1) as f64 * group_height,
+        )
+    } else {
+        (group_widths.sum::<f64>(), group_height)
+    };
+    (
+        (row_width.max(ROW_MIN_WIDTH) + 2.0 * WINDOW_PADDING).ceil(),
+        (row_height.max(ROW_MIN_HEIGHT) + GRABBAR + 2.0 * WINDOW_PADDING).ceil(),
+    )
+}
+
+impl Measurer {
+    /// The logical size of the key window showing `groups` in `typography`,
+    /// `layout` and keys drawn as `key`.
+    pub fn window_size(
+        &self,
+        groups: &[Vec<Key>],
+        typography: &TypographyParameter,
+        layout: Layout,
+        key: KeyBox,
+    ) -> (f64, f64) {
+        let size = typography.fontsize as f32;
+        window_size(groups, typography.fontsize, layout, key, |text| {
+            self.text_width(&typography.fontfamily, size, text)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testutil::groups;
+
+    #[test]
+    fn test_window_size() {
+        // 10px per character
+        let width = |text: &str| text.len() as f32 * 10.0;
+        let horizontal = Layout::Horizontal;
+        let flat = KeyBox::default();
+        assert_eq!(window_size(&[], 12, horizontal, flat, width), (88.0, 48.0));
+        // a key is at least 32 wide, "Enter" is 50 + 4, keys are 2 apart
+        let shown = groups(&[&["Ctrl", "c"], &["Enter"]]);
+        assert_eq!(
+            window_size(&shown, 12, horizontal, flat, width),
+            ((44.0 + 2.0 + 32.0 + 4.0) + (54.0 + 4.0) + 8.0, 48.0)
+        );
+        assert_eq!(
+            window_size(&[], 32, horizontal, flat, width),
+            (88.0, 48.0 + 8.0 + 16.0)
+        );
+        // stacked, as wide as the widest group and a line of 18 + 8 per group
+        assert_eq!(
+            window_size(&shown, 12, Layout::NewestOnTop, flat, width),
+            (44.0 + 2.0 + 32.0 + 4.0 + 8.0, 2.0 * 26.0 + 16.0)
+        );
+        assert_eq!(
+            window_size(&[], 12, Layout::NewestOnBottom, flat, width),
+            window_size(&[], 12, horizontal, flat, width)
+        );
+        // padding and borders of a keycap
+        let keycap = KeyBox {
+            padding: 4.0,
+            border_x: 2.0,
+            border_y: 6.0,
+        };
+        assert_eq!(
+            window_size(&groups(&[&["Backspace"]]), 12, horizontal, keycap, width),
+            (90.0 + 8.0 + 2.0 + 4.0 + 8.0, 18.0 + 8.0 + 6.0 + 4.0 + 16.0)
+        );
+        // an icon is a line square, 4 apart from the label
+        let mut tools = groups(&[&["Tools"]]);
+        tools[0][0].icon = Some("/tools.svg".to_string());
+        assert_eq!(
+            window_size(&tools, 12, horizontal, flat, width),
+            ((18.0 + 4.0 + 50.0 + 4.0) + 4.0 + 8.0, 48.0)
+        );
+    }
+
+    #[test]
+    fn test_text_width() {
+        let measurer = Measurer::system();
+        if measurer.db.is_empty() {
+            eprintln!("skipping: no fonts installed");
+            return;
+        }
+        let narrow = measurer.text_width("SansSerif", 12.0, "i");
+        let wide = measurer.text_width("SansSerif", 12.0, "W");
+        assert!(0.0 < narrow && narrow < wide, "{narrow} {wide}");
+        let twice = measurer.text_width("SansSerif", 24.0, "W");
+        assert!((twice - 2.0 * wide).abs() < 0.01);
+        let mono = |text| measurer.text_width("Monospace", 12.0, text);
+        assert!(mono("Ctrl") > 0.0);
+        // unknown families fall back rather than measuring nothing
+        assert!(measurer.text_width("No Such Font", 12.0, "Ctrl") > 0.0);
+    }
+}
@@ -0,0 +1,411 @@
+use serde::{Deserialize, Serialize};
+
+use crate::keyway::{Key, KeyClass};
+use crate::vkcode;
+
+// evdev key codes, see linux/input-event-codes.h
+const KEY_LEFTCTRL: u16 = 29;
+const KEY_LEFTSHIFT: u16 = 42;
+const KEY_RIGHTSHIFT: u16 = 54;
+const KEY_LEFTALT: u16 = 56;
+const KEY_CAPSLOCK: u16 = 58;
+const KEY_NUMLOCK: u16 = 69;
+const KEY_SCROLLLOCK: u16 = 70;
+const KEY_RIGHTCTRL: u16 = 97;
+const KEY_RIGHTALT: u16 = 100;
+const KEY_LEFTMETA: u16 = 125;
+const KEY_RIGHTMETA: u16 = 126;
+
+// Scan code of the left Ctrl that Windows injects in front of AltGr.
+const SCANCODE_ALTGR_CTRL: u32 = 0x21D;
+const SCANCODE_RSHIFT: u32 = 0x36;
+
+#[cfg(target_os = "windows")]
+const SUPER: &str = "Win";
+#[cfg(not(target_os = "windows"))]
+const SUPER: &str = "Super";
+
+/// A physical modifier or lock key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ModifierKey {
+    LeftShift,
+    RightShift,
+    LeftCtrl,
+    RightCtrl,
+    LeftAlt,
+    RightAlt,
+    LeftSuper,
+    RightSuper,
+    AltGr,
+    CapsLock,
+    NumLock,
+    ScrollLock,
+}
+
+impl ModifierKey {
+    pub fn from_evdev(code: u16) -> Option<Self> {
+        let key = match code {
+            KEY_LEFTSHIFT => ModifierKey::LeftShift,
+            KEY_RIGHTSHIFT => ModifierKey::RightShift,
+            KEY_LEFTCTRL => ModifierKey::LeftCtrl,
+            KEY_RIGHTCTRL => ModifierKey::RightCtrl,
+            KEY_LEFTALT => ModifierKey::LeftAlt,
+            KEY_RIGHTALT => ModifierKey::RightAlt,
+            KEY_LEFTMETA => ModifierKey::LeftSuper,
+            KEY_RIGHTMETA => ModifierKey::RightSuper,
+            KEY_CAPSLOCK => ModifierKey::CapsLock,
+            KEY_NUMLOCK => ModifierKey::NumLock,
+            KEY_SCROLLLOCK => ModifierKey::ScrollLock,
+            _ => return None,
+        };
+        Some(key)
+    }
+
+    /// Maps a virtual-key code from the low level keyboard hook. The generic
+    /// `VK_SHIFT`/`VK_CONTROL`/`VK_MENU` codes fall back to the left key.
+    pub fn from_vk(vk: u16, scancode: u32) -> Option<Self> {
+        let key = match vk {
+            vkcode::VK_SHIFT if scancode == SCANCODE_RSHIFT => ModifierKey::RightShift,
+            vkcode::VK_SHIFT | vkcode::VK_LSHIFT => ModifierKey::LeftShift,
+            vkcode::VK_RSHIFT => ModifierKey::RightShift,
+            vkcode::VK_LCONTROL if scancode == SCANCODE_ALTGR_CTRL => ModifierKey::AltGr,
+            vkcode::VK_CONTROL | vkcode::VK_LCONTROL => ModifierKey::LeftCtrl,
+            vkcode::VK_RCONTROL => ModifierKey::RightCtrl,
+            vkcode::VK_MENU | vkcode::VK_LMENU => ModifierKey::LeftAlt,
+            vkcode::VK_RMENU => ModifierKey::RightAlt,
+            vkcode::VK_LWIN => ModifierKey::LeftSuper,
+            vkcode::VK_RWIN => ModifierKey::RightSuper,
+            vkcode::VK_CAPITAL => ModifierKey::CapsLock,
+            vkcode::VK_NUMLOCK => ModifierKey::NumLock,
+            vkcode::VK_SCROLL => ModifierKey::ScrollLock,
+            _ => return None,
+        };
+        Some(key)
+    }
+
+    pub fn is_lock(&self) -> bool {
+        matches!(
+            self,
+            ModifierKey::CapsLock | ModifierKey::NumLock | ModifierKey::ScrollLock
+        )
+    }
+
+    fn bit(&self) -> u16 {
+        1 << (*self as u16)
+    }
+}
+
+/// Which of the two physical keys holds a modifier. `Any` is used when both
+/// are held or the side is unknown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Side {
+    Any,
+    Left,
+    Right,
+}
+
+impl Side {
+    fn from_keys(left: bool, right: bool) -> Option<Side> {
+        match (left, right) {
+            (true, false) => Some(Side::Left),
+            (false, true) => Some(Side::Right),
+            (true, true) => Some(Side::Any),
+            (false, false) => None,
+        }
+    }
+}
+
+/// The effective modifier set at the time of a key press.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Modifiers {
+    pub shift: Option<Side>,
+    pub ctrl: Option<Side>,
+    pub alt: Option<Side>,
+    #[serde(rename = "super")]
+    pub logo: Option<Side>,
+    pub altgr: bool,
+    pub hyper: bool,
+    /// Lock toggles, reported apart from the labels.
+    pub caps_lock: bool,
+    pub num_lock: bool,
+    pub scroll_lock: bool,
+}
+
+impl Modifiers {
+    /// Labels of the held modifiers in display order, e.g. `["Ctrl", "Shift"]`.
+    /// With `sides` the held side is prefixed, e.g. `["LCtrl", "RShift"]`.
+    pub fn labels(&self, sides: bool) -> Vec<String> {
+        let mut labels = Vec::new();
+        let mut push = |side: Option<Side>, name: &str| match side {
+            Some(Side::Left) if sides => labels.push(format!("L{name}")),
+            Some(Side::Right) if sides => labels.push(format!("R{name}")),
+            Some(_) => labels.push(name.to_string()),
+            None => {}
+        };
+        push(self.ctrl, "Ctrl");
+        push(self.alt, "Alt");
+        push(self.altgr.then_some(Side::Any), "AltGr");
+        push(self.logo, SUPER);
+        push(self.hyper.then_some(Side::Any), "Hyper");
+        push(self.shift, "Shift");
+        labels
+    }
+    /// The labels as keys of the modifier class.
+    pub fn keys(&self, sides: bool) -> Vec<Key> {
+        self.labels(sides)
+            .into_iter()
+            .map(|label| Key::new(label, KeyClass::Modifier))
+            .collect()
+    }
+}
+
+/// Tracks held modifier keys and lock toggles from raw key transitions.
+#[derive(Debug, Clone, Default)]
+pub struct ModifierState {
+    pressed: u16,
+    locked: u16,
+}
+
+impl ModifierState {
+    pub fn new() -> Self {
+        ModifierState::default()
+    }
+    /// Lock keys toggle on the transition to pressed, so auto-repeated
+    /// presses leave them alone.
+    pub fn update(&mut self, key: ModifierKey, pressed: bool) {
+        if pressed {
+            if key.is_lock() && !self.is_pressed(key) {
+                self.locked ^= key.bit();
+            }
+            self.pressed |= key.bit();
+        } else {
+            self.pressed &= !key.bit();
+        }
+    }
+    pub fn is_pressed(&self, key: ModifierKey) -> bool {
+        self.pressed & key.bit() != 0
+    }
+    pub fn is_locked(&self, key: ModifierKey) -> bool {
+        self.locked & key.bit() != 0
+    }
+    pub fn set_locked(&mut self, key: ModifierKey, locked: bool) {
+        if locked {
+            self.locked |= key.bit();
+        } else {
+            self.locked &= !key.bit();
+        }
+    }
+    pub fn modifiers(&self) -> Modifiers {
+        let pressed = |key| self.is_pressed(key);
+        let altgr = pressed(ModifierKey::AltGr);
+        Modifiers {
+            shift: Side::from_keys(
+                pressed(ModifierKey::LeftShift),
+                pressed(ModifierKey::RightShift),
+            ),
+            ctrl: Side::from_keys(
+                pressed(ModifierKey::LeftCtrl),
+                pressed(ModifierKey::RightCtrl),
+            ),
+            // Windows reports AltGr as the right Alt plus an injected Ctrl
+            alt: Side::from_keys(
+                pressed(ModifierKey::LeftAlt),
+                pressed(ModifierKey::RightAlt) && !altgr,
+            ),
+            logo: Side::from_keys(
+                pressed(ModifierKey::LeftSuper),
+                pressed(ModifierKey::RightSuper),
+            ),
+            altgr,
+            hyper: false,
+            caps_lock: self.is_locked(ModifierKey::CapsLock),
+            num_lock: self.is_locked(ModifierKey::NumLock),
+            scroll_lock: self.is_locked(ModifierKey::ScrollLock),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const ALL: [ModifierKey; 12] = [
+        ModifierKey::LeftShift,
+        ModifierKey::RightShift,
+        ModifierKey::LeftCtrl,
+        ModifierKey::RightCtrl,
+        ModifierKey::LeftAlt,
+        ModifierKey::RightAlt,
+        ModifierKey::LeftSuper,
+        ModifierKey::RightSuper,
+        ModifierKey::AltGr,
+        ModifierKey::CapsLock,
+        ModifierKey::NumLock,
+        ModifierKey::ScrollLock,
+    ];
+
+    fn held(keys: &[ModifierKey]) -> ModifierState {
+        let mut state = ModifierState::new();
+        for key in keys {
+            state.update(*key, true);
+        }
+        state
+    }
+
+    #[test]
+    fn test_press_release_every_key() {
+        for key in ALL {
+            let mut state = ModifierState::new();
+            state.update(key, true);
+            assert!(state.is_pressed(key), "{key:?}");
+            for other in ALL.iter().filter(|k| **k != key) {
+                assert!(!state.is_pressed(*other), "{key:?} leaked into {other:?}");
+            }
+            state.update(key, false);
+            assert!(!state.is_pressed(key), "{key:?} stuck");
+            if !key.is_lock() {
+                assert_eq!(state.modifiers(), Modifiers::default(), "{key:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_left_right_are_independent() {
+        let pairs = [
+            (ModifierKey::LeftShift, ModifierKey::RightShift),
+            (ModifierKey::LeftCtrl, ModifierKey::RightCtrl),
+            (ModifierKey::LeftAlt, ModifierKey::RightAlt),
+            (ModifierKey::LeftSuper, ModifierKey::RightSuper),
+        ];
+        let side = |m: Modifiers, key| match key {
+            ModifierKey::LeftShift => m.shift,
+            ModifierKey::LeftCtrl => m.ctrl,
+            ModifierKey::LeftAlt => m.alt,
+            _ => m.logo,
+        };
+        for (left, right) in pairs {
+            let mut state = held(&[left]);
+            assert_eq!(side(state.modifiers(), left), Some(Side::Left));
+            state.update(right, true);
+            assert_eq!(side(state.modifiers(), left), Some(Side::Any));
+            state.update(left, false);
+            assert_eq!(side(state.modifiers(), left), Some(Side::Right));
+            state.update(right, false);
+            assert_eq!(side(state.modifiers(), left), None);
+            assert_eq!(state.modifiers(), Modifiers::default());
+        }
+    }
+
+    #[test]
+    fn test_right_shift_released() {
+        let mut state = held(&[ModifierKey::RightShift]);
+        state.update(ModifierKey::RightShift, false);
+        assert_eq!(state.modifiers().shift, None);
+    }
+
+    #[test]
+    fn test_locks_toggle_on_press_only() {
+        for lock in [
+            ModifierKey::CapsLock,
+            ModifierKey::NumLock,
+            ModifierKey::ScrollLock,
+        ] {
+            let mut state = ModifierState::new();
+            state.update(lock, true);
+            state.update(lock, true);
+            state.update(lock, false);
+            assert!(state.is_locked(lock), "{lock:?}");
+            assert!(!state.is_pressed(lock), "{lock:?}");
+            state.update(lock, true);
+            state.update(lock, false);
+            assert!(!state.is_locked(lock), "{lock:?}");
+        }
+    }
+
+    #[test]
+    fn test_set_locked() {
+        let mut state = ModifierState::new();
+        state.set_locked(ModifierKey::CapsLock, true);
+        assert!(state.is_locked(ModifierKey::CapsLock));
+        assert!(state.modifiers().caps_lock);
+        assert_eq!(state.modifiers().labels(false), Vec::<String>::new());
+        state.update(ModifierKey::CapsLock, true);
+        assert!(!state.is_locked(ModifierKey::CapsLock));
+        assert!(!state.modifiers().caps_lock);
+    }
+
+    #[test]
+    fn test_altgr() {
+        let state = held(&[ModifierKey::AltGr, ModifierKey::RightAlt]);
+        let modifiers = state.modifiers();
+        assert!(modifiers.altgr);
+        assert_eq!(modifiers.alt, None);
+        assert_eq!(modifiers.ctrl, None);
+        assert_eq!(modifiers.labels(false), vec!["AltGr"]);
+    }
+
+    #[test]
+    fn test_labels() {
+        let state = held(&[
+            ModifierKey::LeftShift,
+            ModifierKey::RightCtrl,
+            ModifierKey::LeftSuper,
+            ModifierKey::RightSuper,
+            ModifierKey::CapsLock,
+        ]);
+        let modifiers = state.modifiers();
+        assert!(state.is_locked(ModifierKey::CapsLock));
+        assert_eq!(modifiers.labels(false), vec!["Ctrl", SUPER, "Shift"]);
+        assert_eq!(modifiers.labels(true), vec!["RCtrl", SUPER, "LShift"]);
+    }
+
+    #[test]
+    fn test_from_evdev() {
+        let expected = [
+            (KEY_LEFTSHIFT, ModifierKey::LeftShift),
+            (KEY_RIGHTSHIFT, ModifierKey::RightShift),
+            (KEY_LEFTCTRL, ModifierKey::LeftCtrl),
+            (KEY_RIGHTCTRL, ModifierKey::RightCtrl),
+            (KEY_LEFTALT, ModifierKey::LeftAlt),
+            (KEY_RIGHTALT, ModifierKey::RightAlt),
+            (KEY_LEFTMETA, ModifierKey::LeftSuper),
+            (KEY_RIGHTMETA, ModifierKey::RightSuper),
+            (KEY_CAPSLOCK, ModifierKey::CapsLock),
+            (KEY_NUMLOCK, ModifierKey::NumLock),
+            (KEY_SCROLLLOCK, ModifierKey::ScrollLock),
+        ];
+        for (code, key) in expected {
+            assert_eq!(ModifierKey::from_evdev(code), Some(key));
+        }
+        let mapped = (0..=0x2ff).filter_map(ModifierKey::from_evdev).count();
+        assert_eq!(mapped, expected.len());
+    }
+
+    #[test]
+    fn test_from_vk() {
+        let expected = [
+            (vkcode::VK_SHIFT, 0x2A, ModifierKey::LeftShift),
+            (vkcode::VK_SHIFT, SCANCODE_RSHIFT, ModifierKey::RightShift),
+            (vkcode::VK_LSHIFT, 0x2A, ModifierKey::LeftShift),
+            (vkcode::VK_RSHIFT, SCANCODE_RSHIFT, ModifierKey::RightShift),
+            (vkcode::VK_CONTROL, 0x1D, ModifierKey::LeftCtrl),
+            (vkcode::VK_LCONTROL, 0x1D, ModifierKey::LeftCtrl),
+            (vkcode::VK_LCONTROL, SCANCODE_ALTGR_CTRL, ModifierKey::AltGr),
+            (vkcode::VK_RCONTROL, 0x1D, ModifierKey::RightCtrl),
+            (vkcode::VK_MENU, 0x38, ModifierKey::LeftAlt),
+            (vkcode::VK_LMENU, 0x38, ModifierKey::LeftAlt),
+            (vkcode::VK_RMENU, 0x38, ModifierKey::RightAlt),
+            (vkcode::VK_LWIN, 0x5B, ModifierKey::LeftSuper),
+            (vkcode::VK_RWIN, 0x5C, ModifierKey::RightSuper),
+            (vkcode::VK_CAPITAL, 0x3A, ModifierKey::CapsLock),
+            (vkcode::VK_NUMLOCK, 0x45, ModifierKey::NumLock),
+            (vkcode::VK_SCROLL, 0x46, ModifierKey::ScrollLock),
+        ];
+        for (vk, scancode, key) in expected {
+            assert_eq!(ModifierKey::from_vk(vk, scancode), Some(key), "{vk:#x}");
+        }
+        for vk in (0..=0xff).filter(|vk| !expected.iter().any(|(v, _, _)| v == vk)) {
+            assert_eq!(ModifierKey::from_vk(vk, 0), None, "{vk:#x}");
+        }
+    }
+}
@@ -0,0 +1,382 @@
+//! Size, placement and presence of the key window, driven by the shown keys
+//! and the settings.
+use keyway::config::{
+    BehaviorParameter, Config, DisplayStyle, Follow, Placements, PositionParameter,
+};
+use keyway::desktop::Desktop;
+use keyway::geometry::{clamp_onto, monitor_at, setup_key, Anchor, Rect};
+use keyway::measure::Measurer;
+use keyway::state::AppState;
+use keyway::theme::Themes;
+use keyway::Key;
+use log::{debug, warn};
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use tauri::{LogicalSize, Manager, Monitor, PhysicalPosition, Window, WindowEvent};
+use tokio::sync::watch;
+
+/// The ids of the tray items showing whether clicks go through the key
+/// window and whether it is in move mode.
+pub const CLICK_THROUGH_ITEM: &str = "clickthrough";
+pub const MOVE_ITEM: &str = "move";
+/// Emitted to the key window when it is hidden or shown, or moved.
+const PRESENCE: &str = "on-change-presence";
+
+/// How often the monitor with the pointer or focused window is looked up.
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(500);
+
+fn area(monitor: &Monitor) -> Rect {
+    Rect {
+        x: monitor.position().x,
+        y: monitor.position().y,
+        width: monitor.size().width,
+        height: monitor.size().height,
+    }
+}
+
+/// The monitor the key window belongs on: the one containing `point` when
+/// following, else the chosen one, else the one it is on.
+fn target_monitor(
+    window: &Window,
+    position: &PositionParameter,
+    point: Option<(i32, i32)>,
+) -> tauri::Result<Option<Monitor>> {
+    let mut monitors = window.available_monitors()?;
+    if let Some(point) = point {
+        let areas = monitors.iter().map(area).collect::<Vec<_>>();
+        if let Some(i) = monitor_at(&areas, point) {
+            return Ok(Some(monitors.swap_remove(i)));
+        }
+    }
+    if let (Follow::Off, Some(name)) = (position.follow, &position.monitor) {
+        if let Some(i) = monitors.iter().position(|m| m.name() == Some(name)) {
+            return Ok(Some(monitors.swap_remove(i)));
+        }
+    }
+    window.current_monitor()
+}
+
+/// Moves `window` to its anchor on its target monitor. A free window is only
+/// moved over when it is on another monitor, keeping its offset.
+fn place(
+    window: &Window,
+    position: &PositionParameter,
+    point: Option<(i32, i32)>,
+) -> tauri::Result<()> {
+    let Some(monitor) = target_monitor(window, position, point)? else {
+        return Ok(());
+    };
+    let target = area(&monitor);
+    let margin = (position.margin as f64 * monitor.scale_factor()).round() as i32;
+    let size = window.outer_size()?;
+    let current = window.outer_position()?;
+    let (x, y) = match position
+        .anchor
+        .position(target, size.width, size.height, margin)
+    {
+        Some(anchored) => anchored,
+        None => match window.current_monitor()? {
+            Some(on) if area(&on) != target => (
+                current.x - on.position().x + target.x,
+                current.y - on.position().y + target.y,
+            ),
+            _ => return Ok(()),
+        },
+    };
+    if (current.x, current.y) != (x, y) {
+        window.set_position(PhysicalPosition::new(x, y))?;
+    }
+    Ok(())
+}
+
+fn monitor_areas(window: &Window) -> tauri::Result<Vec<Rect>> {
+    Ok(window.available_monitors()?.iter().map(area).collect())
+}
+
+/// Remembers where a free window was moved to in the current monitor setup.
+fn remember(window: &Window, anchor: Anchor, moved: PhysicalPosition<i32>) {
+    if anchor != Anchor::Free {
+        return;
+    }
+    let Some(state) = window.try_state::<AppState>() else {
+        return;
+    };
+    match monitor_areas(window) {
+        Ok(monitors) => state.set_placement(&setup_key(&monitors), (moved.x, moved.y)),
+        Err(e) => warn!("Failed to list monitors: {e}"),
+    }
+}
+
+/// Moves a free window back to where it was left in this monitor setup,
+/// or in the last one, kept on screen.
+fn restore(window: &Window, placements: &Placements) -> tauri::Result<()> {
+    let monitors = monitor_areas(window)?;
+    let Some(saved) = placements.get(&setup_key(&monitors)) else {
+        return Ok(());
+    };
+    let size = window.outer_size()?;
+    let (x, y) = clamp_onto(&monitors, saved, size.width, size.height);
+    window.set_position(PhysicalPosition::new(x, y))
+}
+
+fn reposition(window: &Window, position: &PositionParameter, point: Option<(i32, i32)>) {
+    place(window, position, point).unwrap_or_else(|e| warn!("Failed to place the key window: {e}"));
+}
+
+/// Where the followed pointer or window is, if following.
+fn followed(desktop: Option<&Desktop>, follow: Follow) -> Option<(i32, i32)> {
+    match follow {
+        Follow::Off => None,
+        Follow::Pointer => desktop?.pointer(),
+        Follow::Focus => desktop?.focused_window().map(|w| w.center()),
+    }
+}
+
+/// Sizes the key window to fit the groups it shows.
+pub struct Sizer {
+    window: Window,
+    config: watch::Receiver<Config>,
+    measurer: Arc<Measurer>,
+    themes: Arc<RwLock<Themes>>,
+    groups: Mutex<Vec<Vec<Key>>>,
+}
+
+impl Sizer {
+    pub fn new(
+        window: Window,
+        config: watch::Receiver<Config>,
+        measurer: Arc<Measurer>,
+        themes: Arc<RwLock<Themes>>,
+    ) -> Self {
+        Sizer {
+            window,
+            config,
+            measurer,
+            themes,
+            groups: Mutex::new(Vec::new()),
+        }
+    }
+    /// Remembers the shown groups and resizes to them.
+    pub fn show(&self, groups: &[Vec<Key>]) {
+        *self.groups.lock().unwrap() = groups.to_vec();
+        self.resize();
+    }
+    /// Resizes to the last shown groups, e.g. after the font or the theme
+    /// changed. The size is logical so Tauri scales it for the monitor the
+    /// window is on.
+    pub fn resize(&self) {
+        let (width, height) = {
+            let groups = self.groups.lock().unwrap();
+            let config = self.config.borrow();
+            let key = self
+                .themes
+                .read()
+                .unwrap()
+                .get(&config.windowappearance.theme)
+                .key_box();
+            self.measurer.window_size(
+                &groups,
+                &config.typography,
+                config.windowappearance.layout,
+                key,
+            )
+        };
+        self.window
+            .set_size(LogicalSize::new(width, height))
+            .unwrap_or_else(|e| warn!("Failed to resize the key window: {e}"));
+    }
+}
+
+/// What makes the key window hidden or ignore the cursor.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Modes {
+    clickthrough: bool,
+    autohide: bool,
+    /// No keys are shown.
+    idle: bool,
+    moving: bool,
+}
+
+impl Modes {
+    /// Hidden while idle when auto-hiding, but never while being moved.
+    fn hidden(self) -> bool {
+        self.autohide && self.idle && !self.moving
+    }
+    /// A hidden window lets clicks through too, a moved one never does.
+    fn ignores_cursor(self) -> bool {
+        !self.moving && (self.clickthrough || self.hidden())
+    }
+}
+
+/// Sent to the key window with [`PRESENCE`].
+#[derive(Debug, Clone, Serialize)]
+struct Shown {
+    hidden: bool,
+    moving: bool,
+}
+
+/// Whether the key window is shown and takes clicks: it lets them through
+/// with click-through, and fades out while idle with the while-inputting
+/// display style. In move mode it is shown and takes clicks so it can be
+/// dragged.
+pub struct Presence {
+    window: Window,
+    modes: Mutex<Modes>,
+}
+
+impl Presence {
+    pub fn new(window: Window) -> Self {
+        Presence {
+            window,
+            modes: Mutex::new(Modes {
+                idle: true,
+                ..Default::default()
+            }),
+        }
+    }
+    fn update<F: FnOnce(&mut Modes)>(&self, f: F) {
+        let mut modes = self.modes.lock().unwrap();
+        let old = *modes;
+        f(&mut modes);
+        if *modes != old {
+            self.apply(*modes);
+        }
+    }
+    /// Applies the click-through and display style settings.
+    pub fn set_behavior(&self, behavior: &BehaviorParameter) {
+        self.update(|modes| {
+            modes.clickthrough = behavior.clickthrough;
+            modes.autohide = behavior.display == DisplayStyle::WhileInputting;
+        });
+    }
+    /// Notes whether any keys are shown.
+    pub fn show(&self, groups: &[Vec<Key>]) {
+        self.update(|modes| modes.idle = groups.is_empty());
+    }
+    /// Switches move mode on or off, returning whether it is now on.
+    pub fn toggle_moving(&self) -> bool {
+        let mut moving = false;
+        self.update(|modes| {
+            modes.moving = !modes.moving;
+            moving = modes.moving;
+        });
+        moving
+    }
+    fn apply(&self, modes: Modes) {
+        self.window
+            .set_ignore_cursor_events(modes.ignores_cursor())
+            .unwrap_or_else(|e| warn!("Failed to set click-through: {e}"));
+        let shown = Shown {
+            hidden: modes.hidden(),
+            moving: modes.moving,
+        };
+        self.window
+            .emit(PRESENCE, shown)
+            .unwrap_or_else(|e| warn!("Failed to emit {PRESENCE}: {e}"));
+        let tray = self.window.app_handle().tray_handle();
+        for (item, selected) in [
+            (CLICK_THROUGH_ITEM, modes.clickthrough),
+            (MOVE_ITEM, modes.moving),
+        ] {
+            tray.get_item(item)
+                .set_selected(selected)
+                .unwrap_or_else(|e| warn!("Failed to update the tray: {e}"));
+        }
+    }
+}
+
+/// Applies the behavior settings to `presence` whenever they change.
+pub async fn follow_behavior(presence: Arc<Presence>, mut config: watch::Receiver<Config>) {
+    loop {
+        presence.set_behavior(&config.borrow_and_update().behavior);
+        if config.changed().await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Keeps `window` at its anchor whenever it is resized or the position
+/// setting changes, and on the followed monitor. A free window is restored
+/// to where it was last moved. `sizer` is refitted when
+/// the typography, the layout or the scale factor changes.
+pub fn start(window: Window, mut config: watch::Receiver<Config>, sizer: Arc<Sizer>) {
+    // taken before any move is remembered over it
+    let placements = config.borrow().placements.clone();
+    // moves while the window is mapped and sized are not the user's
+    let restored = Arc::new(AtomicBool::new(false));
+    {
+        let window_ = window.clone();
+        let config = config.clone();
+        let sizer = sizer.clone();
+        let restored = restored.clone();
+        window.on_window_event(move |event| match event {
+            WindowEvent::Resized(_) => reposition(&window_, &config.borrow().position, None),
+            WindowEvent::Moved(moved) if restored.load(Ordering::Relaxed) => {
+                // not borrowed while remembering, which changes the config
+                let anchor = config.borrow().position.anchor;
+                remember(&window_, anchor, *moved);
+            }
+            WindowEvent::ScaleFactorChanged { .. } => sizer.resize(),
+            _ => {}
+        });
+    }
+    // The Desktop is opened and used on this thread only, Xlib displays
+    // must not move between threads.
+    std::thread::spawn(move || {
+        tauri::async_runtime::block_on(async move {
+            let desktop = Desktop::open();
+            if desktop.is_none() {
+                debug!("No access to the pointer or focused window, following is disabled");
+            }
+            let (mut current, mut look) = {
+                let config = config.borrow_and_update();
+                (
+                    config.position.clone(),
+                    (config.typography.clone(), config.windowappearance.layout),
+                )
+            };
+            sizer.resize();
+            if current.anchor == Anchor::Free {
+                restore(&window, &placements)
+                    .unwrap_or_else(|e| warn!("Failed to restore the key window: {e}"));
+            }
+            restored.store(true, Ordering::Relaxed);
+            reposition(
+                &window,
+                &current,
+                followed(desktop.as_ref(), current.follow),
+            );
+            loop {
+                if current.follow == Follow::Off {
+                    if config.changed().await.is_err() {
+                        return;
+                    }
+                } else if let Ok(changed) =
+                    tokio::time::timeout(FOLLOW_INTERVAL, config.changed()).await
+                {
+                    if changed.is_err() {
+                        return;
+                    }
+                }
+                let (position, changed_look) = {
+                    let config = config.borrow_and_update();
+                    (
+                        config.position.clone(),
+                        (config.typography.clone(), config.windowappearance.layout),
+                    )
+                };
+                if changed_look != look {
+                    sizer.resize();
+                    look = changed_look;
+                }
+                let point = followed(desktop.as_ref(), position.follow);
+                if position != current || point.is_some() {
+                    reposition(&window, &position, point);
+                    current = position;
+                }
+            }
+        })
+    });
+}
@@ -0,0 +1,257 @@
+//! Session recordings.
+//!
+//! A recording is a JSON-lines file. The first line is the header, followed by
+//! one `device` line per input device and then `key` lines in the order the
+//! events were read:
+//!
+//! ```text
+//! {"type":"header","format":"keyway","version":1,"platform":"linux","started":1721900000000}
+//! {"type":"device","id":0,"name":"keyboard","keymap":"xkb_keymap { ... };","locks":["NumLock"]}
+//! {"type":"key","time":0,"device":0,"scancode":30,"virtkey":38,"state":"press"}
+//! {"type":"key","time":84,"device":0,"scancode":30,"virtkey":38,"state":"release"}
+//! ```
+//!
+//! `started` is the wall clock start in milliseconds since the Unix epoch and
+//! `time` the offset of each event from it, taken by the recorder when it
+//! writes the event so it keeps counting when the input backend is restarted.
+//! A restarted backend adds `device` lines between the `key` lines for the
+//! devices that are new or changed, readers keep the last line of each id.
+//! `keymap` is the full xkb keymap on Linux and the keyboard layout
+//! identifier (e.g. `00000409`) on Windows, `locks` the lock keys that were
+//! on when the device was opened. Readers reject files with a newer
+//! `version`.
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::keyway::KeyEvent;
+use crate::modifier::ModifierKey;
+
+pub const FORMAT: &str = "keyway";
+pub const FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Header {
+    pub format: String,
+    pub version: u32,
+    pub platform: String,
+    pub started: u64,
+}
+
+impl Header {
+    pub fn now() -> Self {
+        let started = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        Header {
+            format: FORMAT.to_string(),
+            version: FORMAT_VERSION,
+            platform: std::env::consts::OS.to_string(),
+            started,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeviceInfo {
+    pub id: u32,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keymap: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub locks: Vec<ModifierKey>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Record {
+    Header(Header),
+    Device(DeviceInfo),
+    Key(KeyEvent),
+}
+
+/// Writes a session to a recording, flushing after every record so a crash
+/// keeps everything up to the last event.
+pub struct Recorder {
+    writer: Box<dyn Write + Send>,
+    start: Instant,
+    devices: Vec<DeviceInfo>,
+}
+
+impl Recorder {
+    pub fn new(writer: Box<dyn Write + Send>) -> io::Result<Self> {
+        let mut recorder = Recorder {
+            writer,
+            start: Instant::now(),
+            devices: Vec::new(),
+        };
+        recorder.write(&Record::Header(Header::now()))?;
+        Ok(recorder)
+    }
+    pub fn create(path: &Path) -> io::Result<Self> {
+        Recorder::new(Box::new(BufWriter::new(File::create(path)?)))
+    }
+    /// Records `device`, unless it was recorded as is before the backend
+    /// was restarted.
+    pub fn device(&mut self, device: DeviceInfo) -> io::Result<()> {
+        if self.devices.contains(&device) {
+            return Ok(());
+        }
+        self.write(&Record::Device(device.clone()))?;
+        self.devices.push(device);
+        Ok(())
+    }
+    /// Records `event` at the time since the header.
+    pub fn key(&mut self, event: &KeyEvent) -> io::Result<()> {
+        let event = KeyEvent {
+            time: self.start.elapsed().as_millis() as u64,
+            ..event.clone()
+        };
+        self.write(&Record::Key(event))
+    }
+    fn write(&mut self, record: &Record) -> io::Result<()> {
+        serde_json::to_writer(&mut self.writer, record)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recording {
+    pub header: Header,
+    pub devices: Vec<DeviceInfo>,
+    pub events: Vec<KeyEvent>,
+}
+
+impl Recording {
+    pub fn read<R: BufRead>(reader: R) -> io::Result<Self> {
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+        let mut lines = reader.lines();
+        let header = match lines.next().transpose()? {
+            Some(line) => match serde_json::from_str(&line)? {
+                Record::Header(header) => header,
+                _ => return Err(invalid("recording does not start with a header".into())),
+            },
+            None => return Err(invalid("empty recording".into())),
+        };
+        if header.format != FORMAT {
+            return Err(invalid(format!("unknown format {:?}", header.format)));
+        }
+        if header.version > FORMAT_VERSION {
+            return Err(invalid(format!(
+                "recording version {} is newer than {FORMAT_VERSION}",
+                header.version
+            )));
+        }
+        let mut recording = Recording {
+            header,
+            devices: Vec::new(),
+            events: Vec::new(),
+        };
+        for line in lines {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(&line)? {
+                Record::Device(device) => {
+                    match recording.devices.iter_mut().find(|d| d.id == device.id) {
+                        Some(known) => *known = device,
+                        None => recording.devices.push(device),
+                    }
+                }
+                Record::Key(event) => recording.events.push(event),
+                Record::Header(_) => return Err(invalid("duplicate header".into())),
+            }
+        }
+        Ok(recording)
+    }
+    pub fn open(path: &Path) -> io::Result<Self> {
+        Recording::read(BufReader::new(File::open(path)?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::keyway::KeyState;
+    use crate::testutil::{key, Shared};
+
+    #[test]
+    fn test_roundtrip() {
+        let buf = Shared::default();
+        let mut recorder = Recorder::new(Box::new(buf.clone())).unwrap();
+        let device = DeviceInfo {
+            id: 0,
+            name: "keyboard".to_string(),
+            keymap: None,
+            locks: vec![ModifierKey::NumLock],
+        };
+        recorder.device(device.clone()).unwrap();
+        // times are the recorder's own, whatever the backend reported
+        let events = vec![
+            key(5000, 30, KeyState::Press),
+            key(0, 30, KeyState::Release),
+        ];
+        for event in events.iter() {
+            recorder.key(event).unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        let data = buf.contents();
+        let recording = Recording::read(data.as_slice()).unwrap();
+        assert_eq!(recording.header.version, FORMAT_VERSION);
+        assert_eq!(recording.devices, vec![device]);
+        let times = recording.events.iter().map(|e| e.time).collect::<Vec<_>>();
+        assert!(times[0] < 1000 && times[1] >= times[0] + 20, "{times:?}");
+        let untimed = |events: &[KeyEvent]| {
+            events
+                .iter()
+                .map(|e| KeyEvent {
+                    time: 0,
+                    ..e.clone()
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(untimed(&recording.events), untimed(&events));
+    }
+
+    #[test]
+    fn test_restarted_devices() {
+        let buf = Shared::default();
+        let mut recorder = Recorder::new(Box::new(buf.clone())).unwrap();
+        let device = |id, name: &str| DeviceInfo {
+            id,
+            name: name.to_string(),
+            keymap: None,
+            locks: Vec::new(),
+        };
+        recorder.device(device(0, "keyboard")).unwrap();
+        recorder.key(&key(0, 30, KeyState::Press)).unwrap();
+        // the backend is restarted with a new keymap and a new device
+        recorder.device(device(0, "keyboard")).unwrap();
+        recorder.device(device(0, "keyboard with keymap")).unwrap();
+        recorder.device(device(1, "numpad")).unwrap();
+        let data = buf.contents();
+        assert_eq!(data.as_slice().lines().count(), 5);
+        let recording = Recording::read(data.as_slice()).unwrap();
+        assert_eq!(
+            recording.devices,
+            vec![device(0, "keyboard with keymap"), device(1, "numpad")]
+        );
+        assert_eq!(recording.events.len(), 1);
+    }
+
+    #[test]
+    fn test_rejects_bad_header() {
+        let newer =
+            r#"{"type":"header","format":"keyway","version":99,"platform":"linux","started":0}"#;
+        assert!(Recording::read(newer.as_bytes()).is_err());
+        let headless =
+            r#"{"type":"key","time":0,"device":0,"scancode":30,"virtkey":38,"state":"press"}"#;
+        assert!(Recording::read(headless.as_bytes()).is_err());
+        assert!(Recording::read("".as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,217 @@
+use log::{debug, warn};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::Path;
+#[cfg(feature = "tauri-ui")]
+use tauri::{AppHandle, Manager};
+
+use crate::error::Error;
+use crate::keyway::Key;
+use crate::modifier::Modifiers;
+
+/// Receives the displayed key groups whenever they change.
+pub trait Sink {
+    fn send(&mut self, groups: &[Vec<Key>]) -> io::Result<()>;
+    /// Called when capture failed and is about to be restarted.
+    fn error(&mut self, _error: &Error) {}
+    /// Called when the held modifiers or the lock toggles changed, e.g. to
+    /// show that Caps Lock is on.
+    fn modifiers(&mut self, _modifiers: &Modifiers) {}
+}
+
+impl<F: FnMut(&[Vec<Key>])> Sink for F {
+    fn send(&mut self, groups: &[Vec<Key>]) -> io::Result<()> {
+        self(groups);
+        Ok(())
+    }
+}
+
+/// The event capture errors are emitted as to all windows.
+#[cfg(feature = "tauri-ui")]
+pub const BACKEND_ERROR: &str = "backend-error";
+/// The event the modifiers and lock toggles are emitted as to the window.
+#[cfg(feature = "tauri-ui")]
+pub const MODIFIERS: &str = "on-change-modifiers";
+
+/// Emits the groups to a window of the Tauri app.
+#[cfg(feature = "tauri-ui")]
+pub struct TauriSink {
+    apphandle: AppHandle,
+    label: String,
+    event: String,
+}
+
+#[cfg(feature = "tauri-ui")]
+impl TauriSink {
+    pub fn new(apphandle: AppHandle, label: &str, event: &str) -> Self {
+        TauriSink {
+            apphandle,
+            label: label.to_string(),
+            event: event.to_string(),
+        }
+    }
+}
+
+#[cfg(feature = "tauri-ui")]
+impl Sink for TauriSink {
+    fn send(&mut self, groups: &[Vec<Key>]) -> io::Result<()> {
+        self.apphandle
+            .emit_to(&self.label, &self.event, groups)
+            .map_err(io::Error::other)
+    }
+    fn error(&mut self, error: &Error) {
+        self.apphandle
+            .emit_all(BACKEND_ERROR, error.to_string())
+            .unwrap_or_else(|e| warn!("Failed to emit {BACKEND_ERROR}: {e}"));
+    }
+    fn modifiers(&mut self, modifiers: &Modifiers) {
+        self.apphandle
+            .emit_to(&self.label, MODIFIERS, modifiers)
+            .unwrap_or_else(|e| warn!("Failed to emit {MODIFIERS}: {e}"));
+    }
+}
+
+/// Writes every update as one JSON line, e.g.
+/// `[[{"label":"Ctrl","class":"modifier"},{"label":"c","class":"printable"}]]`.
+pub struct LineSink {
+    writer: Box<dyn Write + Send>,
+}
+
+impl LineSink {
+    pub fn new(writer: Box<dyn Write + Send>) -> Self {
+        LineSink { writer }
+    }
+    pub fn stdout() -> Self {
+        LineSink::new(Box::new(io::stdout()))
+    }
+    pub fn create(path: &Path) -> io::Result<Self> {
+        Ok(LineSink::new(Box::new(BufWriter::new(File::create(path)?))))
+    }
+}
+
+impl Sink for LineSink {
+    fn send(&mut self, groups: &[Vec<Key>]) -> io::Result<()> {
+        serde_json::to_writer(&mut self.writer, groups)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()
+    }
+}
+
+/// Serves the updates as JSON lines to every client connected over TCP.
+/// Clients are accepted on the next update, they get the groups shown before
+/// it followed by the update.
+pub struct TcpSink {
+    listener: TcpListener,
+    clients: Vec<TcpStream>,
+    last: Vec<u8>,
+}
+
+impl TcpSink {
+    pub fn bind(addr: &str) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(TcpSink {
+            listener,
+            clients: Vec::new(),
+            last: b"[]\n".to_vec(),
+        })
+    }
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+    fn accept(&mut self) {
+        while let Ok((mut client, addr)) = self.listener.accept() {
+            debug!("Sink client connected from {addr}");
+            if client.set_nonblocking(true).is_ok() && client.write_all(&self.last).is_ok() {
+                self.clients.push(client);
+            }
+        }
+    }
+}
+
+impl Sink for TcpSink {
+    fn send(&mut self, groups: &[Vec<Key>]) -> io::Result<()> {
+        self.accept();
+        let mut line = serde_json::to_vec(groups)?;
+        line.push(b'\n');
+        self.last = line;
+        // a client that cannot keep up is dropped rather than blocking capture
+        self.clients
+            .retain_mut(|client| client.write_all(&self.last).is_ok());
+        Ok(())
+    }
+}
+
+/// Sends to several sinks, a failing sink does not stop the others.
+#[derive(Default)]
+pub struct Fanout {
+    sinks: Vec<Box<dyn Sink + Send>>,
+}
+
+impl Fanout {
+    pub fn new() -> Self {
+        Fanout::default()
+    }
+    pub fn push(&mut self, sink: Box<dyn Sink + Send>) {
+        self.sinks.push(sink);
+    }
+}
+
+impl Sink for Fanout {
+    fn send(&mut self, groups: &[Vec<Key>]) -> io::Result<()> {
+        for sink in self.sinks.iter_mut() {
+            if let Err(e) = sink.send(groups) {
+                warn!("Failed to send keystrokes: {e}");
+            }
+        }
+        Ok(())
+    }
+    fn error(&mut self, error: &Error) {
+        for sink in self.sinks.iter_mut() {
+            sink.error(error);
+        }
+    }
+    fn modifiers(&mut self, modifiers: &Modifiers) {
+        for sink in self.sinks.iter_mut() {
+            sink.modifiers(modifiers);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::keyway::KeyClass;
+    use crate::testutil::{groups, Shared};
+    use std::io::{BufRead, BufReader};
+
+    #[test]
+    fn test_fanout_lines() {
+        let buf = Shared::default();
+        let mut fanout = Fanout::new();
+        fanout.push(Box::new(LineSink::new(Box::new(buf.clone()))));
+        fanout.push(Box::new(LineSink::new(Box::new(buf.clone()))));
+        let shown = vec![vec![
+            Key::new("Ctrl", KeyClass::Modifier),
+            Key::new("c", KeyClass::Printable),
+        ]];
+        fanout.send(&shown).unwrap();
+        let out = String::from_utf8(buf.contents()).unwrap();
+        let line = r#"[[{"label":"Ctrl","class":"modifier"},{"label":"c","class":"printable"}]]"#;
+        assert_eq!(out, format!("{line}\n{line}\n"));
+    }
+
+    #[test]
+    fn test_tcp() {
+        let mut sink = TcpSink::bind("127.0.0.1:0").unwrap();
+        sink.send(&groups(&[&["a"]])).unwrap();
+        let client = TcpStream::connect(sink.local_addr().unwrap()).unwrap();
+        let mut lines = BufReader::new(client).lines();
+        sink.send(&groups(&[&["a"], &["b"]])).unwrap();
+        let a = r#"{"label":"a","class":"printable"}"#;
+        let b = r#"{"label":"b","class":"printable"}"#;
+        assert_eq!(lines.next().unwrap().unwrap(), format!("[[{a}]]"));
+        assert_eq!(lines.next().unwrap().unwrap(), format!("[[{a}],[{b}]]"));
+    }
+}
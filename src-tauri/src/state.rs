@@ -0,0 +1,137 @@
+//! The settings of the running app.
+//!
+//! [`AppState`] owns the current [`Config`]. Changes go through
+//! [`AppState::set_config`], which validates them, and are published on a
+//! watch channel that the keystroke sender and the windows subscribe to.
+use std::sync::{Arc, RwLock};
+use tokio::sync::watch;
+
+use crate::config::{Config, ConfigPatch, FontFamilies};
+use crate::error::{Error, Result};
+use crate::theme::Themes;
+
+pub struct AppState {
+    config: watch::Sender<Config>,
+    fonts: FontFamilies,
+    themes: Arc<RwLock<Themes>>,
+}
+
+impl AppState {
+    pub fn new(config: Config, fonts: FontFamilies, themes: Arc<RwLock<Themes>>) -> Self {
+        AppState {
+            config: watch::Sender::new(config),
+            fonts,
+            themes,
+        }
+    }
+    pub fn config(&self) -> Config {
+        self.config.borrow().clone()
+    }
+    /// The font families a config may use.
+    pub fn fonts(&self) -> &FontFamilies {
+        &self.fonts
+    }
+    /// A receiver that sees the current config and every later change.
+    pub fn subscribe(&self) -> watch::Receiver<Config> {
+        self.config.subscribe()
+    }
+    /// Validates and applies `patch`, returning the config now in effect.
+    /// Subscribers are only notified if something changed.
+    pub fn set_config(&self, patch: ConfigPatch) -> Result<Config> {
+        let patch = patch.validate(&self.fonts)?;
+        if let Some(appearance) = patch.windowappearance.as_ref() {
+            self.check_theme(&appearance.theme)?;
+        }
+        self.config.send_if_modified(|config| config.apply(patch));
+        Ok(self.config())
+    }
+    /// Rejects a theme that is not loaded, unless it is the one already
+    /// chosen, whose file may only be gone for now.
+    fn check_theme(&self, name: &str) -> Result<()> {
+        if self.config.borrow().windowappearance.theme == name
+            || self.themes.read().unwrap().contains(name)
+        {
+            return Ok(());
+        }
+        Err(Error::Config {
+            field: "theme",
+            message: format!("{name:?} is not a theme"),
+        })
+    }
+    /// Remembers where the key window was moved to in the monitor `setup`.
+    pub fn set_placement(&self, setup: &str, position: (i32, i32)) {
+        self.config
+            .send_if_modified(|config| config.placements.set(setup, position));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::{BehaviorParameter, TypographyParameter, WindowAppearanceParameter};
+
+    #[test]
+    fn test_subscribers_see_changes() {
+        let state = AppState::new(
+            Config::default(),
+            FontFamilies::default(),
+            Arc::new(RwLock::new(Themes::new())),
+        );
+        let mut rx = state.subscribe();
+        assert!(!rx.has_changed().unwrap());
+
+        let behavior = ConfigPatch {
+            behavior: Some(BehaviorParameter {
+                timeout: 10,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let config = state.set_config(behavior.clone()).unwrap();
+        assert_eq!(config.behavior.timeout, 50);
+        assert!(rx.has_changed().unwrap());
+        assert_eq!(*rx.borrow_and_update(), config);
+
+        // an unchanged value does not wake subscribers
+        state.set_config(behavior).unwrap();
+        assert!(!rx.has_changed().unwrap());
+
+        // a rejected section keeps the valid ones from being applied too
+        let mixed = ConfigPatch {
+            behavior: Some(BehaviorParameter {
+                timeout: 1000,
+                ..Default::default()
+            }),
+            typography: Some(TypographyParameter {
+                textcolor: "nope".to_string(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert!(matches!(state.set_config(mixed), Err(Error::Config { .. })));
+        assert!(!rx.has_changed().unwrap());
+        assert_eq!(state.config(), config);
+    }
+
+    #[test]
+    fn test_unknown_theme() {
+        let state = AppState::new(
+            Config::default(),
+            FontFamilies::default(),
+            Arc::new(RwLock::new(Themes::new())),
+        );
+        let theme = |theme: &str| ConfigPatch {
+            windowappearance: Some(WindowAppearanceParameter {
+                theme: theme.to_string(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert!(matches!(
+            state.set_config(theme("mechanicl")),
+            Err(Error::Config { field: "theme", .. })
+        ));
+        let config = state.set_config(theme("mechanical")).unwrap();
+        assert_eq!(config.windowappearance.theme, "mechanical");
+    }
+}
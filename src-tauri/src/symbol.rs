@@ -0,0 +1,309 @@
+use log::{debug, warn};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+use crate::keyway::KeyClass;
+
+pub const LABELS_FILE: &str = "labels.json";
+
+/// A user defined replacement for the label of a key.
+///
+/// Written either as a plain string (`"F13": "Layer↑"`) or as an object
+/// carrying an icon (`"F13": { "label": "Layer↑", "icon": "layer-up.svg" }`).
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum Label {
+    Text(String),
+    Full {
+        label: String,
+        #[serde(default)]
+        icon: Option<String>,
+    },
+}
+
+impl Label {
+    pub fn text(&self) -> &str {
+        match self {
+            Label::Text(text) => text,
+            Label::Full { label, .. } => label,
+        }
+    }
+    pub fn icon(&self) -> Option<&str> {
+        match self {
+            Label::Text(_) => None,
+            Label::Full { icon, .. } => icon.as_deref(),
+        }
+    }
+}
+
+/// Contents of the labels file.
+///
+/// `keysyms` is keyed by the xkb keysym name on Linux (`F13`, `XF86Tools`)
+/// and by the built-in key name on Windows. `scancodes` is keyed by the evdev
+/// key code on Linux and by the hardware scan code on Windows, and wins over
+/// `keysyms` when both match.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct Overrides {
+    #[serde(default)]
+    keysyms: HashMap<String, Label>,
+    #[serde(default)]
+    scancodes: HashMap<u32, Label>,
+}
+
+impl Overrides {
+    pub fn from_json(s: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(s)
+    }
+    /// Resolves relative icon paths against `dir`, the labels file's folder.
+    fn resolve_icons(mut self, dir: &Path) -> Self {
+        for label in self.keysyms.values_mut().chain(self.scancodes.values_mut()) {
+            if let Label::Full {
+                icon: Some(icon), ..
+            } = label
+            {
+                *icon = dir.join(&*icon).to_string_lossy().into_owned();
+            }
+        }
+        self
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SymbolMap {
+    path: Option<PathBuf>,
+    modified: Option<SystemTime>,
+    overrides: Overrides,
+}
+
+impl SymbolMap {
+    pub fn new() -> Self {
+        SymbolMap::default()
+    }
+    pub fn load(path: &Path) -> Self {
+        let mut symbols = SymbolMap {
+            path: Some(path.to_path_buf()),
+            ..SymbolMap::default()
+        };
+        symbols.reload_if_changed();
+        symbols
+    }
+    /// Re-reads the labels file when its modification time differs from the
+    /// last load. A missing file clears the overrides, a malformed one keeps
+    /// the previous overrides.
+    pub fn reload_if_changed(&mut self) -> bool {
+        let Some(path) = self.path.as_ref() else {
+            return false;
+        };
+        let modified = fs::metadata(path).and_then(|m| m.modified()).ok();
+        if modified == self.modified {
+            return false;
+        }
+        self.modified = modified;
+        if modified.is_none() {
+            debug!("Labels file {:?} removed", path);
+            self.overrides = Overrides::default();
+            return true;
+        }
+        match fs::read_to_string(path).map(|s| Overrides::from_json(&s)) {
+            Ok(Ok(overrides)) => {
+                debug!("Loaded labels file {:?}", path);
+                let dir = path.parent().unwrap_or(Path::new(""));
+                self.overrides = overrides.resolve_icons(dir);
+                true
+            }
+            Ok(Err(e)) => {
+                warn!("Failed to parse labels file {:?}: {e}", path);
+                false
+            }
+            Err(e) => {
+                warn!("Failed to read labels file {:?}: {e}", path);
+                false
+            }
+        }
+    }
+    pub fn lookup(&self, keysym: &str, scancode: u32) -> Option<&Label> {
+        self.overrides
+            .scancodes
+            .get(&scancode)
+            .or_else(|| self.overrides.keysyms.get(keysym))
+    }
+}
+
+impl From<Overrides> for SymbolMap {
+    fn from(overrides: Overrides) -> Self {
+        SymbolMap {
+            overrides,
+            ..SymbolMap::default()
+        }
+    }
+}
+
+/// Display label for an xkb keysym name whose character is not printable,
+/// matching the key names used on Windows. Dead keys show their spacing
+/// accent as Windows does. Unknown names are shown as is.
+pub fn keysym_label(name: &str) -> &str {
+    match name {
+        "Return" => "Enter",
+        "KP_Enter" => "NumEnter",
+        "BackSpace" => "Backspace",
+        "Escape" => "Esc",
+        "Tab" | "ISO_Left_Tab" => "Tab",
+        "space" => "Space",
+        "Prior" | "KP_Prior" => "PageUp",
+        "Next" | "KP_Next" => "PageDown",
+        "Home" | "KP_Home" => "Home",
+        "End" | "KP_End" => "End",
+        "Left" | "KP_Left" => "Left",
+        "Right" | "KP_Right" => "Right",
+        "Up" | "KP_Up" => "Up",
+        "Down" | "KP_Down" => "Down",
+        "Insert" | "KP_Insert" => "Insert",
+        "Delete" | "KP_Delete" => "Delete",
+        "Caps_Lock" => "CapsLock",
+        "Num_Lock" => "NumLock",
+        "Scroll_Lock" => "ScrollLock",
+        "Print" | "Sys_Req" => "PrintScreen",
+        "Pause" | "Break" => "Pause",
+        "Menu" => "Menu",
+        "Shift_L" | "Shift_R" => "Shift",
+        "Control_L" | "Control_R" => "Ctrl",
+        "Alt_L" | "Alt_R" | "Meta_L" | "Meta_R" => "Alt",
+        "Super_L" | "Super_R" => "Super",
+        "Hyper_L" | "Hyper_R" => "Hyper",
+        "ISO_Level3_Shift" | "Mode_switch" => "AltGr",
+        "XF86AudioMute" => "Mute",
+        "XF86AudioLowerVolume" => "VolumeDown",
+        "XF86AudioRaiseVolume" => "VolumeUp",
+        "XF86AudioNext" => "NextTrack",
+        "XF86AudioPrev" => "PrevTrack",
+        "XF86AudioStop" => "Stop",
+        "XF86AudioPlay" | "XF86AudioPause" => "PlayPause",
+        "dead_grave" => "`",
+        "dead_acute" => "´",
+        "dead_circumflex" => "^",
+        "dead_tilde" => "~",
+        "dead_macron" => "¯",
+        "dead_breve" => "˘",
+        "dead_abovedot" => "˙",
+        "dead_diaeresis" => "¨",
+        "dead_abovering" => "˚",
+        "dead_doubleacute" => "˝",
+        "dead_caron" => "ˇ",
+        "dead_cedilla" => "¸",
+        "dead_ogonek" => "˛",
+        _ => name,
+    }
+}
+
+/// The class of the key producing the xkb keysym `name`.
+pub fn keysym_class(name: &str) -> KeyClass {
+    let name = name.strip_prefix("KP_").unwrap_or(name);
+    match name {
+        "Left" | "Right" | "Up" | "Down" | "Home" | "End" | "Prior" | "Next" | "Page_Up"
+        | "Page_Down" | "Begin" => KeyClass::Navigation,
+        "Return" | "Enter" | "BackSpace" | "Tab" | "ISO_Left_Tab" | "Delete" | "Insert"
+        | "Clear" | "Undo" | "Redo" => KeyClass::Editing,
+        "Caps_Lock" | "Num_Lock" | "Scroll_Lock" | "Shift_Lock" => KeyClass::Modifier,
+        "Escape" | "Print" | "Sys_Req" | "Pause" | "Break" | "Menu" | "Help" | "Cancel" => {
+            KeyClass::Function
+        }
+        _ if name.starts_with("XF86") => KeyClass::Function,
+        _ if name.starts_with('F') && name[1..].parse::<u8>().is_ok() => KeyClass::Function,
+        _ => KeyClass::Printable,
+    }
+}
+
+/// Polls the labels file every `interval` and swaps in the new overrides.
+pub fn watch(symbols: Arc<RwLock<SymbolMap>>, interval: Duration) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(interval);
+        symbols.write().unwrap().reload_if_changed();
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_overrides_lookup() {
+        let overrides = Overrides::from_json(
+            r#"{
+                "keysyms": {
+                    "F13": "Layer↑",
+                    "XF86Tools": { "label": "Tools", "icon": "tools.svg" }
+                },
+                "scancodes": { "183": "Layer↓" }
+            }"#,
+        )
+        .unwrap();
+        let symbols = SymbolMap::from(overrides);
+        assert_eq!(symbols.lookup("F13", 0).map(Label::text), Some("Layer↑"));
+        assert_eq!(symbols.lookup("F13", 183).map(Label::text), Some("Layer↓"));
+        let tools = symbols.lookup("XF86Tools", 0).unwrap();
+        assert_eq!(tools.text(), "Tools");
+        assert_eq!(tools.icon(), Some("tools.svg"));
+        assert_eq!(symbols.lookup("a", 30), None);
+    }
+
+    #[test]
+    fn test_keysym_label() {
+        assert_eq!(keysym_label("Return"), "Enter");
+        assert_eq!(keysym_label("ISO_Level3_Shift"), "AltGr");
+        assert_eq!(keysym_label("F13"), "F13");
+        assert_eq!(keysym_label("XF86Tools"), "XF86Tools");
+        assert_eq!(keysym_label("dead_acute"), "´");
+        assert_eq!(keysym_label("dead_circumflex"), "^");
+    }
+
+    #[test]
+    fn test_keysym_class() {
+        assert_eq!(keysym_class("a"), KeyClass::Printable);
+        assert_eq!(keysym_class("space"), KeyClass::Printable);
+        assert_eq!(keysym_class("KP_5"), KeyClass::Printable);
+        assert_eq!(keysym_class("KP_Left"), KeyClass::Navigation);
+        assert_eq!(keysym_class("Prior"), KeyClass::Navigation);
+        assert_eq!(keysym_class("BackSpace"), KeyClass::Editing);
+        assert_eq!(keysym_class("KP_Enter"), KeyClass::Editing);
+        assert_eq!(keysym_class("F12"), KeyClass::Function);
+        assert_eq!(keysym_class("XF86AudioMute"), KeyClass::Function);
+        assert_eq!(keysym_class("Caps_Lock"), KeyClass::Modifier);
+        assert_eq!(keysym_class("Fabricated"), KeyClass::Printable);
+    }
+
+    #[test]
+    fn test_reload_if_changed() {
+        let path = std::env::temp_dir().join(format!("keyway-labels-{}.json", std::process::id()));
+        fs::write(&path, r#"{ "keysyms": { "F13": "Layer↑" } }"#).unwrap();
+        let mut symbols = SymbolMap::load(&path);
+        assert_eq!(symbols.lookup("F13", 0).map(Label::text), Some("Layer↑"));
+        assert!(!symbols.reload_if_changed());
+
+        fs::write(&path, "not json").unwrap();
+        symbols.modified = None;
+        symbols.reload_if_changed();
+        assert_eq!(symbols.lookup("F13", 0).map(Label::text), Some("Layer↑"));
+
+        // icons are found next to the labels file
+        fs::write(
+            &path,
+            r#"{ "scancodes": { "183": { "label": "", "icon": "up.svg" } } }"#,
+        )
+        .unwrap();
+        symbols.modified = None;
+        symbols.reload_if_changed();
+        let icon = std::env::temp_dir().join("up.svg");
+        assert_eq!(
+            symbols.lookup("F13", 183).and_then(Label::icon),
+            icon.to_str()
+        );
+
+        fs::remove_file(&path).unwrap();
+        assert!(symbols.reload_if_changed());
+        assert_eq!(symbols.lookup("F13", 0), None);
+    }
+}
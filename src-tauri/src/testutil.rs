@@ -0,0 +1,47 @@
+//! Fixtures shared by the unit tests.
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+use crate::keyway::{Key, KeyClass, KeyEvent, KeyState};
+
+/// A writer whose output can still be read once it was handed away.
+#[derive(Clone, Default)]
+pub struct Shared(Arc<Mutex<Vec<u8>>>);
+
+impl Shared {
+    /// Everything written so far.
+    pub fn contents(&self) -> Vec<u8> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+impl Write for Shared {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A group of printable keys.
+pub fn group(keys: &[&str]) -> Vec<Key> {
+    keys.iter()
+        .map(|k| Key::new(*k, KeyClass::Printable))
+        .collect()
+}
+
+pub fn groups(keys: &[&[&str]]) -> Vec<Vec<Key>> {
+    keys.iter().map(|g| group(g)).collect()
+}
+
+/// An event of device 0 with the xkb keycode of the evdev `scancode`.
+pub fn key(time: u64, scancode: u32, state: KeyState) -> KeyEvent {
+    KeyEvent {
+        time,
+        device: 0,
+        scancode,
+        virtkey: scancode + 8,
+        state,
+    }
+}
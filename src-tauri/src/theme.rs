@@ -0,0 +1,374 @@
+//! Keycap themes: the built-in ones and JSON files in the themes directory,
+//! named after their file stem. A file named after a built-in theme replaces
+//! it, missing fields take the values of the flat theme. Each class of keys
+//! can have its own label and face colors.
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::config::parse_color;
+use crate::error::{Error, Result};
+use crate::keyway::KeyClass;
+use crate::measure::KeyBox;
+
+pub const THEMES_DIR: &str = "themes";
+pub const DEFAULT_THEME: &str = "flat";
+
+const MAX_RADIUS: u32 = 16;
+const MAX_PADDING: u32 = 12;
+const MAX_SHADOW: u32 = 8;
+
+/// How a keycap is drawn.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum KeycapStyle {
+    /// Just the label on its face.
+    #[default]
+    Flat,
+    /// Lifted off the window by a drop shadow.
+    Elevated,
+    /// A border that is `shadow` pixels deeper at the bottom.
+    Mechanical,
+    /// A border and no face.
+    Outlined,
+}
+
+/// Label and face colors of a class of keys, None for those of all keys.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClassColors {
+    pub textcolor: Option<String>,
+    pub backgroundcolor: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    pub keycap: KeycapStyle,
+    /// Label and face colors, None for the typography text color and no
+    /// face.
+    pub textcolor: Option<String>,
+    pub backgroundcolor: Option<String>,
+    /// Colors by class, a class left out looks like any other key.
+    pub classes: BTreeMap<KeyClass, ClassColors>,
+    pub bordercolor: String,
+    pub shadowcolor: String,
+    /// Corner radius, padding and shadow depth in logical pixels.
+    pub radius: u32,
+    pub padding: u32,
+    pub shadow: u32,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            keycap: KeycapStyle::Flat,
+            textcolor: None,
+            backgroundcolor: None,
+            classes: classes(&[
+                (KeyClass::Modifier, Some("#3b82f6"), None),
+                (KeyClass::Navigation, Some("#22c55e"), None),
+                (KeyClass::Function, Some("#ef4444"), None),
+                (KeyClass::Editing, Some("#f59e0b"), None),
+                (KeyClass::Mouse, Some("#a855f7"), None),
+            ]),
+            bordercolor: "#808080".to_string(),
+            shadowcolor: "#00000080".to_string(),
+            radius: 4,
+            padding: 2,
+            shadow: 0,
+        }
+    }
+}
+
+fn color(c: &str) -> Option<String> {
+    Some(c.to_string())
+}
+
+/// The class colors from `(class, textcolor, backgroundcolor)` triples.
+fn classes(colors: &[(KeyClass, Option<&str>, Option<&str>)]) -> BTreeMap<KeyClass, ClassColors> {
+    colors
+        .iter()
+        .map(|(class, text, background)| {
+            let colors = ClassColors {
+                textcolor: text.and_then(color),
+                backgroundcolor: background.and_then(color),
+            };
+            (*class, colors)
+        })
+        .collect()
+}
+
+/// The themes available without any files.
+fn builtin() -> BTreeMap<String, Theme> {
+    let elevated = Theme {
+        keycap: KeycapStyle::Elevated,
+        textcolor: color("#202020"),
+        backgroundcolor: color("#f5f5f5"),
+        classes: classes(&[
+            (KeyClass::Modifier, Some("#1e3a8a"), Some("#dbeafe")),
+            (KeyClass::Navigation, Some("#14532d"), Some("#dcfce7")),
+            (KeyClass::Function, Some("#7f1d1d"), Some("#fee2e2")),
+            (KeyClass::Editing, Some("#78350f"), Some("#fef3c7")),
+            (KeyClass::Mouse, Some("#581c87"), Some("#f3e8ff")),
+        ]),
+        shadowcolor: "#00000066".to_string(),
+        radius: 6,
+        padding: 4,
+        shadow: 2,
+        ..Theme::default()
+    };
+    let mechanical = Theme {
+        keycap: KeycapStyle::Mechanical,
+        textcolor: color("#f0f0f0"),
+        backgroundcolor: color("#3a3a3a"),
+        classes: classes(&[
+            (KeyClass::Modifier, Some("#fde68a"), Some("#4a4033")),
+            (KeyClass::Navigation, Some("#bbf7d0"), Some("#2f4536")),
+            (KeyClass::Function, Some("#fecaca"), Some("#4a3030")),
+            (KeyClass::Editing, Some("#bfdbfe"), Some("#303b4a")),
+            (KeyClass::Mouse, Some("#e9d5ff"), Some("#3f3049")),
+        ]),
+        bordercolor: "#1a1a1a".to_string(),
+        radius: 4,
+        padding: 4,
+        shadow: 4,
+        ..Theme::default()
+    };
+    let outlined = Theme {
+        keycap: KeycapStyle::Outlined,
+        classes: classes(&[
+            (KeyClass::Modifier, Some("#7dd3fc"), None),
+            (KeyClass::Navigation, Some("#86efac"), None),
+            (KeyClass::Function, Some("#fca5a5"), None),
+            (KeyClass::Editing, Some("#fcd34d"), None),
+            (KeyClass::Mouse, Some("#d8b4fe"), None),
+        ]),
+        bordercolor: "#e0e0e0".to_string(),
+        radius: 6,
+        padding: 3,
+        ..Theme::default()
+    };
+    [
+        (DEFAULT_THEME, Theme::default()),
+        ("elevated", elevated),
+        ("mechanical", mechanical),
+        ("outlined", outlined),
+    ]
+    .into_iter()
+    .map(|(name, theme)| (name.to_string(), theme))
+    .collect()
+}
+
+impl Theme {
+    pub fn from_json(s: &str) -> Result<Self> {
+        serde_json::from_str::<Theme>(s)?.validate()
+    }
+    /// Normalizes the colors and clamps the lengths.
+    pub fn validate(mut self) -> Result<Self> {
+        let optional = |field, c: Option<String>| c.map(|c| parse_color(field, &c)).transpose();
+        self.textcolor = optional("textcolor", self.textcolor)?;
+        self.backgroundcolor = optional("backgroundcolor", self.backgroundcolor)?;
+        for colors in self.classes.values_mut() {
+            colors.textcolor = optional("classes.textcolor", colors.textcolor.take())?;
+            colors.backgroundcolor =
+                optional("classes.backgroundcolor", colors.backgroundcolor.take())?;
+        }
+        self.bordercolor = parse_color("bordercolor", &self.bordercolor)?;
+        self.shadowcolor = parse_color("shadowcolor", &self.shadowcolor)?;
+        self.radius = self.radius.min(MAX_RADIUS);
+        self.padding = self.padding.min(MAX_PADDING);
+        self.shadow = self.shadow.min(MAX_SHADOW);
+        Ok(self)
+    }
+    /// The space around a label, as drawn by `src/KeyWindow/index.tsx`.
+    pub fn key_box(&self) -> KeyBox {
+        let (border_x, border_y) = match self.keycap {
+            KeycapStyle::Flat | KeycapStyle::Elevated => (0, 0),
+            KeycapStyle::Outlined => (2, 2),
+            KeycapStyle::Mechanical => (2, 2 + self.shadow),
+        };
+        KeyBox {
+            padding: self.padding as f64,
+            border_x: border_x as f64,
+            border_y: border_y as f64,
+        }
+    }
+}
+
+/// The theme files in `dir` with their modification times.
+fn scan(dir: &Path) -> Vec<(PathBuf, Option<SystemTime>)> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut files = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .map(|path| {
+            let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+            (path, modified)
+        })
+        .collect::<Vec<_>>();
+    files.sort();
+    files
+}
+
+/// The built-in themes and those in a themes directory.
+#[derive(Debug)]
+pub struct Themes {
+    dir: Option<PathBuf>,
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+    themes: BTreeMap<String, Theme>,
+}
+
+impl Default for Themes {
+    fn default() -> Self {
+        Themes {
+            dir: None,
+            files: Vec::new(),
+            themes: builtin(),
+        }
+    }
+}
+
+impl Themes {
+    pub fn new() -> Self {
+        Themes::default()
+    }
+    pub fn load(dir: &Path) -> Self {
+        let mut themes = Themes {
+            dir: Some(dir.to_path_buf()),
+            ..Themes::default()
+        };
+        themes.reload_if_changed();
+        themes
+    }
+    /// Re-reads the theme files when one was added, removed or modified. A
+    /// malformed file keeps the theme loaded before under its name.
+    pub fn reload_if_changed(&mut self) -> bool {
+        let Some(dir) = self.dir.as_ref() else {
+            return false;
+        };
+        let files = scan(dir);
+        if files == self.files {
+            return false;
+        }
+        let mut themes = builtin();
+        for (path, _) in files.iter() {
+            let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+            let loaded = fs::read_to_string(path)
+                .map_err(Error::from)
+                .and_then(|s| Theme::from_json(&s));
+            match loaded {
+                Ok(theme) => {
+                    debug!("Loaded theme {:?}", path);
+                    themes.insert(name.to_string(), theme);
+                }
+                Err(e) => {
+                    warn!("Ignoring theme {:?}: {e}", path);
+                    if let Some(previous) = self.themes.get(name) {
+                        themes.insert(name.to_string(), previous.clone());
+                    }
+                }
+            }
+        }
+        self.files = files;
+        self.themes = themes;
+        true
+    }
+    pub fn contains(&self, name: &str) -> bool {
+        self.themes.contains_key(name)
+    }
+    pub fn names(&self) -> Vec<String> {
+        self.themes.keys().cloned().collect()
+    }
+    /// The theme called `name`, or the flat one if there is none.
+    pub fn get(&self, name: &str) -> Theme {
+        self.themes.get(name).cloned().unwrap_or_else(|| {
+            debug!("No theme {name:?}, using {DEFAULT_THEME:?}");
+            Theme::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_from_json() {
+        let theme =
+            Theme::from_json(r##"{"keycap": "outlined", "bordercolor": "#FFF", "padding": 100}"##)
+                .unwrap();
+        assert_eq!(theme.keycap, KeycapStyle::Outlined);
+        assert_eq!(theme.bordercolor, "#ffffff");
+        assert_eq!(theme.padding, MAX_PADDING);
+        assert_eq!(theme.textcolor, None);
+        let theme =
+            Theme::from_json(r##"{"classes": {"function": {"textcolor": "#F00"}}}"##).unwrap();
+        assert_eq!(theme.classes.len(), 1);
+        assert_eq!(
+            theme.classes[&KeyClass::Function].textcolor.as_deref(),
+            Some("#ff0000")
+        );
+        assert!(matches!(
+            Theme::from_json(r#"{"classes": {"modifier": {"textcolor": "blue"}}}"#),
+            Err(Error::Config {
+                field: "classes.textcolor",
+                ..
+            })
+        ));
+        assert!(Theme::from_json(r#"{"classes": {"keypad": {}}}"#).is_err());
+        for theme in builtin().into_values() {
+            assert_eq!(theme.clone().validate().unwrap(), theme);
+        }
+    }
+
+    #[test]
+    fn test_key_box() {
+        let theme = |keycap| Theme {
+            keycap,
+            padding: 3,
+            shadow: 4,
+            ..Theme::default()
+        };
+        assert_eq!(theme(KeycapStyle::Flat).key_box().border_y, 0.0);
+        assert_eq!(theme(KeycapStyle::Outlined).key_box().border_x, 2.0);
+        let mechanical = theme(KeycapStyle::Mechanical).key_box();
+        assert_eq!((mechanical.padding, mechanical.border_y), (3.0, 6.0));
+    }
+
+    #[test]
+    fn test_reload() {
+        let dir = std::env::temp_dir().join(format!("keyway-themes-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut themes = Themes::load(&dir);
+        assert_eq!(
+            themes.names(),
+            ["elevated", "flat", "mechanical", "outlined"]
+        );
+        assert!(!themes.reload_if_changed());
+
+        fs::write(dir.join("flat.json"), r#"{"radius": 0}"#).unwrap();
+        fs::write(dir.join("mine.json"), r#"{"keycap": "elevated"}"#).unwrap();
+        fs::write(dir.join("notes.txt"), "not a theme").unwrap();
+        assert!(themes.reload_if_changed());
+        assert_eq!(themes.get("flat").radius, 0);
+        assert_eq!(themes.get("mine").keycap, KeycapStyle::Elevated);
+        assert_eq!(themes.names().len(), 5);
+
+        // a broken file keeps the theme, a removed one goes
+        fs::write(dir.join("mine.json"), "{").unwrap();
+        fs::remove_file(dir.join("flat.json")).unwrap();
+        assert!(themes.reload_if_changed());
+        assert_eq!(themes.get("mine").keycap, KeycapStyle::Elevated);
+        assert_eq!(themes.get("flat"), Theme::default());
+        assert_eq!(themes.get("gone"), Theme::default());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,331 @@
+//! Windows virtual-key codes and their display names.
+//!
+//! Kept free of the `windows` crate so the table can be unit tested on every
+//! platform.
+use crate::keyway::KeyClass;
+
+pub const VK_LBUTTON: u16 = 0x01;
+pub const VK_RBUTTON: u16 = 0x02;
+pub const VK_CANCEL: u16 = 0x03;
+pub const VK_MBUTTON: u16 = 0x04;
+pub const VK_XBUTTON1: u16 = 0x05;
+pub const VK_XBUTTON2: u16 = 0x06;
+pub const VK_BACK: u16 = 0x08;
+pub const VK_TAB: u16 = 0x09;
+pub const VK_CLEAR: u16 = 0x0C;
+pub const VK_RETURN: u16 = 0x0D;
+pub const VK_SHIFT: u16 = 0x10;
+pub const VK_CONTROL: u16 = 0x11;
+pub const VK_MENU: u16 = 0x12;
+pub const VK_PAUSE: u16 = 0x13;
+pub const VK_CAPITAL: u16 = 0x14;
+pub const VK_KANA: u16 = 0x15;
+pub const VK_KANJI: u16 = 0x19;
+pub const VK_ESCAPE: u16 = 0x1B;
+pub const VK_CONVERT: u16 = 0x1C;
+pub const VK_NONCONVERT: u16 = 0x1D;
+pub const VK_SPACE: u16 = 0x20;
+pub const VK_PRIOR: u16 = 0x21;
+pub const VK_NEXT: u16 = 0x22;
+pub const VK_END: u16 = 0x23;
+pub const VK_HOME: u16 = 0x24;
+pub const VK_LEFT: u16 = 0x25;
+pub const VK_UP: u16 = 0x26;
+pub const VK_RIGHT: u16 = 0x27;
+pub const VK_DOWN: u16 = 0x28;
+pub const VK_SELECT: u16 = 0x29;
+pub const VK_PRINT: u16 = 0x2A;
+pub const VK_EXECUTE: u16 = 0x2B;
+pub const VK_SNAPSHOT: u16 = 0x2C;
+pub const VK_INSERT: u16 = 0x2D;
+pub const VK_DELETE: u16 = 0x2E;
+pub const VK_HELP: u16 = 0x2F;
+pub const VK_0: u16 = 0x30;
+pub const VK_9: u16 = 0x39;
+pub const VK_A: u16 = 0x41;
+pub const VK_Z: u16 = 0x5A;
+pub const VK_LWIN: u16 = 0x5B;
+pub const VK_RWIN: u16 = 0x5C;
+pub const VK_APPS: u16 = 0x5D;
+pub const VK_SLEEP: u16 = 0x5F;
+pub const VK_NUMPAD0: u16 = 0x60;
+pub const VK_NUMPAD9: u16 = 0x69;
+pub const VK_MULTIPLY: u16 = 0x6A;
+pub const VK_ADD: u16 = 0x6B;
+pub const VK_SEPARATOR: u16 = 0x6C;
+pub const VK_SUBTRACT: u16 = 0x6D;
+pub const VK_DECIMAL: u16 = 0x6E;
+pub const VK_DIVIDE: u16 = 0x6F;
+pub const VK_F1: u16 = 0x70;
+pub const VK_F24: u16 = 0x87;
+pub const VK_NUMLOCK: u16 = 0x90;
+pub const VK_SCROLL: u16 = 0x91;
+pub const VK_LSHIFT: u16 = 0xA0;
+pub const VK_RSHIFT: u16 = 0xA1;
+pub const VK_LCONTROL: u16 = 0xA2;
+pub const VK_RCONTROL: u16 = 0xA3;
+pub const VK_LMENU: u16 = 0xA4;
+pub const VK_RMENU: u16 = 0xA5;
+pub const VK_BROWSER_BACK: u16 = 0xA6;
+pub const VK_BROWSER_FORWARD: u16 = 0xA7;
+pub const VK_BROWSER_REFRESH: u16 = 0xA8;
+pub const VK_BROWSER_STOP: u16 = 0xA9;
+pub const VK_BROWSER_SEARCH: u16 = 0xAA;
+pub const VK_BROWSER_FAVORITES: u16 = 0xAB;
+pub const VK_BROWSER_HOME: u16 = 0xAC;
+pub const VK_VOLUME_MUTE: u16 = 0xAD;
+pub const VK_VOLUME_DOWN: u16 = 0xAE;
+pub const VK_VOLUME_UP: u16 = 0xAF;
+pub const VK_MEDIA_NEXT_TRACK: u16 = 0xB0;
+pub const VK_MEDIA_PREV_TRACK: u16 = 0xB1;
+pub const VK_MEDIA_STOP: u16 = 0xB2;
+pub const VK_MEDIA_PLAY_PAUSE: u16 = 0xB3;
+pub const VK_LAUNCH_MAIL: u16 = 0xB4;
+pub const VK_LAUNCH_MEDIA_SELECT: u16 = 0xB5;
+pub const VK_LAUNCH_APP1: u16 = 0xB6;
+pub const VK_LAUNCH_APP2: u16 = 0xB7;
+pub const VK_OEM_1: u16 = 0xBA;
+pub const VK_OEM_PLUS: u16 = 0xBB;
+pub const VK_OEM_COMMA: u16 = 0xBC;
+pub const VK_OEM_MINUS: u16 = 0xBD;
+pub const VK_OEM_PERIOD: u16 = 0xBE;
+pub const VK_OEM_2: u16 = 0xBF;
+pub const VK_OEM_3: u16 = 0xC0;
+pub const VK_OEM_4: u16 = 0xDB;
+pub const VK_OEM_5: u16 = 0xDC;
+pub const VK_OEM_6: u16 = 0xDD;
+pub const VK_OEM_7: u16 = 0xDE;
+pub const VK_OEM_8: u16 = 0xDF;
+pub const VK_OEM_102: u16 = 0xE2;
+
+const DIGITS: [&str; 10] = ["0", "1", "2", "3", "4", "5", "6", "7", "8", "9"];
+const SHIFTED_DIGITS: [char; 10] = [')', '!', '@', '#', '$', '%', '^', '&', '*', '('];
+const LETTERS: [&str; 26] = [
+    "A", "B", "C", "D", "E", "F", "G", "H", "I", "J", "K", "L", "M", "N", "O", "P", "Q", "R", "S",
+    "T", "U", "V", "W", "X", "Y", "Z",
+];
+const NUMPAD: [&str; 10] = [
+    "Num0", "Num1", "Num2", "Num3", "Num4", "Num5", "Num6", "Num7", "Num8", "Num9",
+];
+const FUNCTIONS: [&str; 24] = [
+    "F1", "F2", "F3", "F4", "F5", "F6", "F7", "F8", "F9", "F10", "F11", "F12", "F13", "F14", "F15",
+    "F16", "F17", "F18", "F19", "F20", "F21", "F22", "F23", "F24",
+];
+
+/// Display name of a virtual-key code, independent of the keyboard layout.
+pub fn name(vk: u16) -> Option<&'static str> {
+    let name = match vk {
+        VK_LBUTTON => "LBUTTON",
+        VK_RBUTTON => "RBUTTON",
+        VK_CANCEL => "Break",
+        VK_MBUTTON => "MBUTTON",
+        VK_XBUTTON1 => "XBUTTON1",
+        VK_XBUTTON2 => "XBUTTON2",
+        VK_BACK => "Backspace",
+        VK_TAB => "Tab",
+        VK_CLEAR => "Clear",
+        VK_RETURN => "Enter",
+        VK_SHIFT | VK_LSHIFT | VK_RSHIFT => "Shift",
+        VK_CONTROL | VK_LCONTROL | VK_RCONTROL => "Ctrl",
+        VK_MENU | VK_LMENU | VK_RMENU => "Alt",
+        VK_PAUSE => "Pause",
+        VK_CAPITAL => "CapsLock",
+        VK_KANA => "Kana",
+        VK_KANJI => "Kanji",
+        VK_ESCAPE => "Esc",
+        VK_CONVERT => "Convert",
+        VK_NONCONVERT => "NonConvert",
+        VK_SPACE => "Space",
+        VK_PRIOR => "PageUp",
+        VK_NEXT => "PageDown",
+        VK_END => "End",
+        VK_HOME => "Home",
+        VK_LEFT => "Left",
+        VK_UP => "Up",
+        VK_RIGHT => "Right",
+        VK_DOWN => "Down",
+        VK_SELECT => "Select",
+        VK_PRINT => "Print",
+        VK_EXECUTE => "Execute",
+        VK_SNAPSHOT => "PrintScreen",
+        VK_INSERT => "Insert",
+        VK_DELETE => "Delete",
+        VK_HELP => "Help",
+        VK_0..=VK_9 => DIGITS[(vk - VK_0) as usize],
+        VK_A..=VK_Z => LETTERS[(vk - VK_A) as usize],
+        VK_LWIN | VK_RWIN => "Win",
+        VK_APPS => "Menu",
+        VK_SLEEP => "Sleep",
+        VK_NUMPAD0..=VK_NUMPAD9 => NUMPAD[(vk - VK_NUMPAD0) as usize],
+        VK_MULTIPLY => "Num*",
+        VK_ADD => "Num+",
+        VK_SEPARATOR => "Num,",
+        VK_SUBTRACT => "Num-",
+        VK_DECIMAL => "Num.",
+        VK_DIVIDE => "Num/",
+        VK_F1..=VK_F24 => FUNCTIONS[(vk - VK_F1) as usize],
+        VK_NUMLOCK => "NumLock",
+        VK_SCROLL => "ScrollLock",
+        VK_BROWSER_BACK => "BrowserBack",
+        VK_BROWSER_FORWARD => "BrowserForward",
+        VK_BROWSER_REFRESH => "BrowserRefresh",
+        VK_BROWSER_STOP => "BrowserStop",
+        VK_BROWSER_SEARCH => "BrowserSearch",
+        VK_BROWSER_FAVORITES => "BrowserFavorites",
+        VK_BROWSER_HOME => "BrowserHome",
+        VK_VOLUME_MUTE => "Mute",
+        VK_VOLUME_DOWN => "VolumeDown",
+        VK_VOLUME_UP => "VolumeUp",
+        VK_MEDIA_NEXT_TRACK => "NextTrack",
+        VK_MEDIA_PREV_TRACK => "PrevTrack",
+        VK_MEDIA_STOP => "Stop",
+        VK_MEDIA_PLAY_PAUSE => "PlayPause",
+        VK_LAUNCH_MAIL => "Mail",
+        VK_LAUNCH_MEDIA_SELECT => "Media",
+        VK_LAUNCH_APP1 => "App1",
+        VK_LAUNCH_APP2 => "App2",
+        VK_OEM_1 => ";",
+        VK_OEM_PLUS => "+",
+        VK_OEM_COMMA => ",",
+        VK_OEM_MINUS => "-",
+        VK_OEM_PERIOD => ".",
+        VK_OEM_2 => "/",
+        VK_OEM_3 => "`",
+        VK_OEM_4 => "[",
+        VK_OEM_5 => "\\",
+        VK_OEM_6 => "]",
+        VK_OEM_7 => "'",
+        VK_OEM_8 => "OEM8",
+        VK_OEM_102 => "<",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// The class a virtual-key code is styled as.
+pub fn class(vk: u16) -> KeyClass {
+    match vk {
+        VK_LBUTTON | VK_RBUTTON | VK_MBUTTON | VK_XBUTTON1 | VK_XBUTTON2 => KeyClass::Mouse,
+        VK_SHIFT..=VK_MENU | VK_LSHIFT..=VK_RMENU | VK_LWIN | VK_RWIN => KeyClass::Modifier,
+        VK_CAPITAL | VK_NUMLOCK | VK_SCROLL => KeyClass::Modifier,
+        VK_PRIOR..=VK_DOWN => KeyClass::Navigation,
+        VK_BACK | VK_TAB | VK_CLEAR | VK_RETURN | VK_INSERT | VK_DELETE => KeyClass::Editing,
+        VK_SPACE | VK_NUMPAD0..=VK_DIVIDE => KeyClass::Printable,
+        _ if is_printable(vk) => KeyClass::Printable,
+        _ => KeyClass::Function,
+    }
+}
+
+/// Keys whose label depends on the active keyboard layout.
+pub fn is_printable(vk: u16) -> bool {
+    matches!(
+        vk,
+        VK_0..=VK_9 | VK_A..=VK_Z | VK_OEM_1..=VK_OEM_3 | VK_OEM_4..=VK_OEM_8 | VK_OEM_102
+    )
+}
+
+/// Character produced by `vk` on the US layout, used when the active layout
+/// cannot be queried.
+pub fn translate_us(vk: u16, shift: bool, caps_lock: bool) -> Option<char> {
+    let (base, shifted) = match vk {
+        VK_0..=VK_9 => (
+            (b'0' + (vk - VK_0) as u8) as char,
+            SHIFTED_DIGITS[(vk - VK_0) as usize],
+        ),
+        VK_A..=VK_Z => {
+            let upper = (b'A' + (vk - VK_A) as u8) as char;
+            let lower = upper.to_ascii_lowercase();
+            return Some(if shift != caps_lock { upper } else { lower });
+        }
+        VK_OEM_1 => (';', ':'),
+        VK_OEM_PLUS => ('=', '+'),
+        VK_OEM_COMMA => (',', '<'),
+        VK_OEM_MINUS => ('-', '_'),
+        VK_OEM_PERIOD => ('.', '>'),
+        VK_OEM_2 => ('/', '?'),
+        VK_OEM_3 => ('`', '~'),
+        VK_OEM_4 => ('[', '{'),
+        VK_OEM_5 | VK_OEM_102 => ('\\', '|'),
+        VK_OEM_6 => (']', '}'),
+        VK_OEM_7 => ('\'', '"'),
+        _ => return None,
+    };
+    Some(if shift { shifted } else { base })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_name() {
+        assert_eq!(name(VK_LWIN), Some("Win"));
+        assert_eq!(name(VK_RCONTROL), Some("Ctrl"));
+        assert_eq!(name(VK_OEM_1), Some(";"));
+        assert_eq!(name(VK_OEM_2), Some("/"));
+        assert_eq!(name(VK_OEM_4), Some("["));
+        assert_eq!(name(VK_OEM_6), Some("]"));
+        assert_eq!(name(VK_OEM_7), Some("'"));
+        assert_eq!(name(VK_VOLUME_UP), Some("VolumeUp"));
+        assert_eq!(name(VK_MEDIA_PLAY_PAUSE), Some("PlayPause"));
+        assert_eq!(name(0x30), Some("0"));
+        assert_eq!(name(0x47), Some("G"));
+        assert_eq!(name(0x65), Some("Num5"));
+        assert_eq!(name(0x7B), Some("F12"));
+        assert_eq!(name(VK_F24), Some("F24"));
+        assert_eq!(name(0x07), None);
+        assert_eq!(name(0xFF), None);
+    }
+
+    #[test]
+    fn test_name_covers_ranges() {
+        for vk in (VK_0..=VK_9)
+            .chain(VK_A..=VK_Z)
+            .chain(VK_NUMPAD0..=VK_DIVIDE)
+            .chain(VK_F1..=VK_F24)
+            .chain(VK_LSHIFT..=VK_LAUNCH_APP2)
+        {
+            assert!(name(vk).is_some(), "missing name for {vk:#x}");
+        }
+    }
+
+    #[test]
+    fn test_class() {
+        assert_eq!(class(VK_LBUTTON), KeyClass::Mouse);
+        assert_eq!(class(VK_RCONTROL), KeyClass::Modifier);
+        assert_eq!(class(VK_CAPITAL), KeyClass::Modifier);
+        assert_eq!(class(VK_HOME), KeyClass::Navigation);
+        assert_eq!(class(VK_DOWN), KeyClass::Navigation);
+        assert_eq!(class(VK_RETURN), KeyClass::Editing);
+        assert_eq!(class(VK_DELETE), KeyClass::Editing);
+        assert_eq!(class(0x50), KeyClass::Printable);
+        assert_eq!(class(VK_OEM_2), KeyClass::Printable);
+        assert_eq!(class(0x74), KeyClass::Function);
+        assert_eq!(class(VK_ESCAPE), KeyClass::Function);
+        assert_eq!(class(VK_VOLUME_UP), KeyClass::Function);
+    }
+
+    #[test]
+    fn test_printable_keys_translate() {
+        for vk in (0..=0xFF).filter(|vk| is_printable(*vk) && *vk != VK_OEM_8) {
+            assert!(translate_us(vk, false, false).is_some(), "{vk:#x}");
+            assert!(translate_us(vk, true, false).is_some(), "{vk:#x}");
+        }
+    }
+
+    #[test]
+    fn test_translate_us() {
+        assert_eq!(translate_us(0x41, false, false), Some('a'));
+        assert_eq!(translate_us(0x41, true, false), Some('A'));
+        assert_eq!(translate_us(0x41, false, true), Some('A'));
+        assert_eq!(translate_us(0x41, true, true), Some('a'));
+        assert_eq!(translate_us(0x31, false, false), Some('1'));
+        assert_eq!(translate_us(0x31, true, false), Some('!'));
+        assert_eq!(translate_us(0x30, true, false), Some(')'));
+        assert_eq!(translate_us(0x31, false, true), Some('1'));
+        assert_eq!(translate_us(VK_OEM_1, true, false), Some(':'));
+        assert_eq!(translate_us(VK_OEM_2, true, false), Some('?'));
+        assert_eq!(translate_us(VK_OEM_7, true, false), Some('"'));
+        assert_eq!(translate_us(VK_RETURN, false, false), None);
+    }
+}
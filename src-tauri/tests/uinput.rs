@@ -0,0 +1,175 @@
+//! Types on a virtual keyboard and checks what the pipeline shows for it.
+//! Needs write access to /dev/uinput and is skipped without it.
+#![cfg(all(target_os = "linux", feature = "evdev"))]
+
+use evdev::uinput::{VirtualDevice, VirtualDeviceBuilder};
+use evdev::{AttributeSet, Device, EventType, InputEvent, Key as EvdevKey};
+use std::path::PathBuf;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+use keyway::config::Config;
+use keyway::error::Result;
+use keyway::keysender::{run_pipeline, EvdevBackend, InputBackend};
+use keyway::recorder::DeviceInfo;
+use keyway::symbol::SymbolMap;
+use keyway::{Key, KeyEvent, KeyState};
+
+const KEY_STATE_RELEASE: i32 = 0;
+const KEY_STATE_PRESS: i32 = 1;
+
+/// Creates a virtual keyboard with `codes` through /dev/uinput and opens its
+/// event node, or returns None when uinput is not available.
+fn virtual_keyboard(codes: &[EvdevKey]) -> Option<(VirtualDevice, PathBuf, Device)> {
+    let mut keys = AttributeSet::<EvdevKey>::new();
+    for code in codes {
+        keys.insert(*code);
+    }
+    let mut virt = VirtualDeviceBuilder::new()
+        .ok()?
+        .name("keyway test keyboard")
+        .with_keys(&keys)
+        .ok()?
+        .build()
+        .ok()?;
+    let path = virt
+        .enumerate_dev_nodes_blocking()
+        .ok()?
+        .find_map(|path| path.ok())?;
+    // the node shows up before udev has applied its permissions
+    for _ in 0..20 {
+        if let Ok(device) = Device::open(&path) {
+            return Some((virt, path, device));
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    None
+}
+
+/// The evdev backend, finished once `expected` events were read or after a
+/// deadline, so the pipeline returns.
+struct Typed {
+    backend: EvdevBackend,
+    expected: usize,
+    received: Vec<KeyEvent>,
+    deadline: Instant,
+}
+
+impl InputBackend for Typed {
+    fn devices(&self) -> Vec<DeviceInfo> {
+        self.backend.devices()
+    }
+    fn poll(&mut self, timeout: Duration) -> Result<Vec<KeyEvent>> {
+        let events = self.backend.poll(timeout)?;
+        self.received.extend(events.iter().cloned());
+        Ok(events)
+    }
+    fn is_finished(&self) -> bool {
+        self.received.len() >= self.expected || Instant::now() > self.deadline
+    }
+}
+
+#[test]
+fn test_uinput_pipeline() {
+    let (a, b, c) = (EvdevKey::KEY_A, EvdevKey::KEY_B, EvdevKey::KEY_C);
+    let (shift, ctrl, enter) = (
+        EvdevKey::KEY_LEFTSHIFT,
+        EvdevKey::KEY_LEFTCTRL,
+        EvdevKey::KEY_ENTER,
+    );
+    let Some((mut virt, path, device)) = virtual_keyboard(&[a, b, c, shift, ctrl, enter]) else {
+        eprintln!("skipping: /dev/uinput not available");
+        return;
+    };
+    // the devices get the keymap of the XKB_DEFAULT_* variables
+    std::env::set_var("XKB_DEFAULT_LAYOUT", "us");
+    std::env::remove_var("XKB_DEFAULT_VARIANT");
+    std::env::remove_var("XKB_DEFAULT_OPTIONS");
+
+    // a, Shift+b, Ctrl+c, Enter
+    let typed = [
+        (a, true),
+        (a, false),
+        (shift, true),
+        (b, true),
+        (b, false),
+        (shift, false),
+        (ctrl, true),
+        (c, true),
+        (c, false),
+        (ctrl, false),
+        (enter, true),
+        (enter, false),
+    ];
+    let mut backend = Typed {
+        backend: EvdevBackend::with_devices(vec![(path, device)]).unwrap(),
+        expected: typed.len(),
+        received: Vec::new(),
+        deadline: Instant::now() + Duration::from_secs(5),
+    };
+    for (key, pressed) in typed {
+        let value = if pressed {
+            KEY_STATE_PRESS
+        } else {
+            KEY_STATE_RELEASE
+        };
+        virt.emit(&[InputEvent::new(EventType::KEY, key.code(), value)])
+            .unwrap();
+    }
+
+    let mut config = Config::default();
+    config.behavior.timeout = 500;
+    let config = watch::Sender::new(config).subscribe();
+    let mut shown: Vec<Vec<Key>> = Vec::new();
+    run_pipeline(
+        &mut backend,
+        &config,
+        &RwLock::new(SymbolMap::new()),
+        &mut None,
+        &mut |groups: &[Vec<Key>]| {
+            if groups.len() > shown.len() {
+                shown = groups.to_vec();
+            }
+        },
+    )
+    .unwrap();
+
+    let received = backend
+        .received
+        .iter()
+        .map(|e| (e.scancode as u16, e.state))
+        .collect::<Vec<_>>();
+    let expected = typed
+        .iter()
+        .map(|(key, pressed)| {
+            let state = if *pressed {
+                KeyState::Press
+            } else {
+                KeyState::Release
+            };
+            (key.code(), state)
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(received, expected);
+    let labels = shown
+        .iter()
+        .map(|group| {
+            group
+                .iter()
+                .map(|key| key.label.as_str())
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        labels,
+        vec![
+            vec!["a"],
+            vec!["Shift"],
+            vec!["Shift", "B"],
+            vec!["Ctrl"],
+            vec!["Ctrl", "c"],
+            vec!["Enter"],
+        ]
+    );
+}
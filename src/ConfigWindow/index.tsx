@@ -1,6 +1,7 @@
-import { WebviewWindow } from "@tauri-apps/api/window"
-import { useState } from "react"
-import { BehaviorParameter, TypographyParameter, WindowAppearanceParameter } from "../parameter";
+import { listen, Event, UnlistenFn } from "@tauri-apps/api/event"
+import { invoke } from "@tauri-apps/api/tauri"
+import { useEffect, useState } from "react"
+import { Anchor, BehaviorParameter, Config, ConfigPatch, DisplayStyle, FontFamily, Follow, Layout, MonitorInfo, PositionParameter, TypographyParameter, WindowAppearanceParameter } from "../parameter";
 
 // const TitleBar: React.FC = () => {
 //   return (
@@ -12,16 +13,67 @@ import { BehaviorParameter, TypographyParameter, WindowAppearanceParameter } fro
 //   )
 // }
 
-const config_window: WebviewWindow = new WebviewWindow("ConfigWindow");
+// Sends a change to the backend, which answers with the config in effect.
+const set_config = (patch: ConfigPatch): Promise<Config> => {
+    return invoke<Config>("set_config", { patch: patch });
+}
+
+// Shows the config in effect, and again whenever it is changed elsewhere,
+// e.g. from the tray.
+const useConfig = (show: (config: Config) => void) => {
+    useEffect(() => {
+        invoke<Config>("get_config").then(show);
+        let unlisten: UnlistenFn;
+        listen("on-change-config", (event: Event<Config>) => show(event.payload))
+            .then(f => { unlisten = f; });
+        return () => {
+            if (unlisten) {
+                unlisten();
+            }
+        }
+    }, []);
+}
+
+// Shown below a section when the backend rejected a setting.
+const ConfigError: React.FC<{ error: string | null }> = ({ error }) => {
+    if (error === null) {
+        return null;
+    }
+    return <div className="col-span-4 text-sm text-error">{error}</div>
+}
 
 const Behavior: React.FC = () => {
     const [timeout, setTimeout] = useState(500);
     const [mousevisible, setMouseVisible] = useState(false);
     const [modvisible, setModVisible] = useState(false);
+    const [modifierside, setModifierSide] = useState(false);
+    const [clickthrough, setClickThrough] = useState(false);
+    const [display, setDisplay] = useState<DisplayStyle>("always");
     const behavior_param: BehaviorParameter = {
         timeout: timeout,
         mousevisible: mousevisible,
-        modvisible: modvisible
+        modvisible: modvisible,
+        modifierside: modifierside,
+        clickthrough: clickthrough,
+        display: display,
+    };
+    const [error, setError] = useState<string | null>(null);
+    const show = (applied: BehaviorParameter) => {
+        setTimeout(applied.timeout);
+        setMouseVisible(applied.mousevisible);
+        setModVisible(applied.modvisible);
+        setModifierSide(applied.modifierside);
+        setClickThrough(applied.clickthrough);
+        setDisplay(applied.display);
+    };
+    useConfig(config => show(config.behavior));
+    const set_behavior = (param: BehaviorParameter) => {
+        set_config({ behavior: param })
+            .then(config => {
+                setError(null);
+                show(config.behavior);
+            })
+            .catch(e => setError(String(e)));
     };
     return (
         <div className="grid grid-cols-4 gap-4 m-4">
@@ -35,9 +87,8 @@ const Behavior: React.FC = () => {
                     value={timeout}
                     className="range range-sm range-primary"
                     onChange={e => {
-                        behavior_param.timeout = parseInt(e.target.value);
-                        config_window.emit("on-change-behavior", behavior_param);
                         setTimeout(parseInt(e.target.value));
+                        set_behavior({ ...behavior_param, timeout: parseInt(e.target.value) });
                     }}
                 />
                 <span>{timeout}ms</span>
@@ -49,9 +100,8 @@ const Behavior: React.FC = () => {
                     className="toggle toggle-primary"
                     checked={mousevisible}
                     onChange={(e) => {
-                        behavior_param.mousevisible = mousevisible;
-                        config_window.emit("on-change-behavior", behavior_param);
-                        setMouseVisible(e.target.checked)
+                        setMouseVisible(e.target.checked);
+                        set_behavior({ ...behavior_param, mousevisible: e.target.checked });
                     }}
                 />
                 <span>
@@ -68,9 +118,8 @@ const Behavior: React.FC = () => {
                     className="toggle toggle-primary"
                     checked={modvisible}
                     onChange={(e) => {
-                        behavior_param.modvisible = modvisible;
-                        config_window.emit("on-change-behavior", behavior_param);
-                        setModVisible(e.target.checked)
+                        setModVisible(e.target.checked);
+                        set_behavior({ ...behavior_param, modvisible: e.target.checked });
                     }}
                 />
                 <span>
@@ -80,6 +129,57 @@ const Behavior: React.FC = () => {
                     }
                 </span>
             </div>
+            <div className="col-span-1">Left/Right</div>
+            <div className="col-span-3 flex flex-row gap-2">
+                <input
+                    type="checkbox"
+                    className="toggle toggle-primary"
+                    checked={modifierside}
+                    onChange={(e) => {
+                        setModifierSide(e.target.checked);
+                        set_behavior({ ...behavior_param, modifierside: e.target.checked });
+                    }}
+                />
+                <span>
+                    {modifierside
+                        ? "LCtrl"
+                        : "Ctrl"
+                    }
+                </span>
+            </div>
+            <div className="col-span-1">Click-through</div>
+            <div className="col-span-3 flex flex-row gap-2">
+                <input
+                    type="checkbox"
+                    className="toggle toggle-primary"
+                    checked={clickthrough}
+                    onChange={(e) => {
+                        setClickThrough(e.target.checked);
+                        set_behavior({ ...behavior_param, clickthrough: e.target.checked });
+                    }}
+                />
+                <span>
+                    {clickthrough
+                        ? "Clicks go through, use \"Move\" in the tray to drag"
+                        : "Off"
+                    }
+                </span>
+            </div>
+            <div className="col-span-1">Display</div>
+            <div className="col-span-3 flex flex-row gap-2">
+                <select
+                    className="select select-sm select-bordered w-full"
+                    value={display}
+                    onChange={e => {
+                        setDisplay(e.target.value as DisplayStyle);
+                        set_behavior({ ...behavior_param, display: e.target.value as DisplayStyle });
+                    }}
+                >
+                    <option value="always">Always</option>
+                    <option value="while-inputting">While inputting</option>
+                </select>
+            </div>
+            <ConfigError error={error} />
         </div>
     )
 }
@@ -88,29 +188,61 @@ const Typography: React.FC = () => {
     const [fontsize, setFontSize] = useState(12);
     const [fontfamily, setFontFamily] = useState("SansSerif");
     const [textcolor, setTextColor] = useState("#e0e0e0");
+    const [fonts, setFonts] = useState<Array<FontFamily>>([]);
     const typography_param: TypographyParameter = {
         fontsize: fontsize,
         fontfamily: fontfamily,
         textcolor: textcolor,
     };
+    const [error, setError] = useState<string | null>(null);
+    const show = (applied: TypographyParameter) => {
+        setFontSize(applied.fontsize);
+        setFontFamily(applied.fontfamily);
+        setTextColor(applied.textcolor);
+    };
+    useConfig(config => show(config.typography));
+    useEffect(() => {
+        invoke<Array<FontFamily>>("list_fonts").then(setFonts);
+    }, []);
+    const fontOptions = (families: Array<FontFamily>) => families.map(f =>
+        <option key={f.name} value={f.name} style={{ fontFamily: f.name }}>{f.name}</option>
+    );
+    const set_typography = (param: TypographyParameter) => {
+        set_config({ typography: param })
+            .then(config => {
+                setError(null);
+                show(config.typography);
+            })
+            .catch(e => setError(String(e)));
+    };
     return (
         <div className="grid grid-cols-4 gap-4 m-4">
             {/************ Fontfamily ************/}
             <div className="col-span-1">Fontfamily</div>
-            <div className="col-span-3 flex flex-row gap-2">
+            <div className="col-span-3 flex flex-col gap-2">
                 <select
                     className="select select-sm select-bordered w-full"
                     value={fontfamily}
                     onChange={e => {
-                        typography_param.fontfamily = e.target.value;
-                        config_window.emit("on-change-typography", typography_param);
-                        setFontFamily(e.target.value)
+                        setFontFamily(e.target.value);
+                        set_typography({ ...typography_param, fontfamily: e.target.value });
                     }}
                 >
-                    <option value="SansSerif">SansSerif</option>
-                    <option value="Monospace">Monospace</option>
-                    <option value="Consolas">Consolas</option>
+                    {!fonts.some(f => f.name === fontfamily)
+                        && <option value={fontfamily}>{fontfamily}</option>}
+                    <optgroup label="Generic">
+                        {fontOptions(fonts.filter(f => f.generic))}
+                    </optgroup>
+                    <optgroup label="Monospace">
+                        {fontOptions(fonts.filter(f => !f.generic && f.monospace))}
+                    </optgroup>
+                    <optgroup label="Proportional">
+                        {fontOptions(fonts.filter(f => !f.generic && !f.monospace))}
+                    </optgroup>
                 </select>
+                <span className="truncate" style={{ fontFamily: fontfamily, fontSize: fontsize }}>
+                    Ctrl Shift Enter ←↑→↓ 0123
+                </span>
             </div>
             {/************ Fontsize ************/}
             <div className="col-span-1">Fontsize</div>
@@ -123,9 +255,8 @@ const Typography: React.FC = () => {
                     value={fontsize}
                     className="range range-sm range-primary"
                     onChange={(e) => {
-                        typography_param.fontsize = parseInt(e.target.value);
-                        config_window.emit("on-change-typography", typography_param);
-                        setFontSize(parseInt(e.target.value))
+                        setFontSize(parseInt(e.target.value));
+                        set_typography({ ...typography_param, fontsize: parseInt(e.target.value) });
                     }}
                 />
                 <span>{fontsize}</span>
@@ -137,13 +268,13 @@ const Typography: React.FC = () => {
                     type="color"
                     value={textcolor}
                     onChange={e => {
-                        typography_param.textcolor = e.target.value;
-                        config_window.emit("on-change-typography", typography_param);
-                        setTextColor(e.target.value)
+                        setTextColor(e.target.value);
+                        set_typography({ ...typography_param, textcolor: e.target.value });
                     }}
                 />
                 <span>{textcolor}</span>
             </div>
+            <ConfigError error={error} />
         </div>
     )
 }
@@ -152,10 +283,44 @@ const WindowAppearance: React.FC = () => {
     const [backgroundcolor, setBackgroundColor] = useState("#e0e0e0");
     const [transparenttoggle, setTransparentToggle] = useState(true);
     const [backgroundopacity, setBackgroundOpacity] = useState(50);
+    const [layout, setLayout] = useState<Layout>("horizontal");
+    const [theme, setTheme] = useState("flat");
+    const [themes, setThemes] = useState<Array<string>>([]);
     const windowappearance_param: WindowAppearanceParameter = {
         backgroundcolor: backgroundcolor,
         transparantetoggle: transparenttoggle,
         backgroundopacity: backgroundopacity,
+        layout: layout,
+        theme: theme,
+    };
+    const [error, setError] = useState<string | null>(null);
+    const show = (applied: WindowAppearanceParameter) => {
+        setBackgroundColor(applied.backgroundcolor);
+        setTransparentToggle(applied.transparantetoggle);
+        setBackgroundOpacity(applied.backgroundopacity);
+        setLayout(applied.layout);
+        setTheme(applied.theme);
+    };
+    useConfig(config => show(config.windowappearance));
+    // the themes directory is watched, files may come and go
+    useEffect(() => {
+        invoke<Array<string>>("list_themes").then(setThemes);
+        let unlisten: UnlistenFn;
+        listen("on-change-themes", (event: Event<Array<string>>) => setThemes(event.payload))
+            .then(f => { unlisten = f; });
+        return () => {
+            if (unlisten) {
+                unlisten();
+            }
+        }
+    }, []);
+    const set_windowappearance = (param: WindowAppearanceParameter) => {
+        set_config({ windowappearance: param })
+            .then(config => {
+                setError(null);
+                show(config.windowappearance);
+            })
+            .catch(e => setError(String(e)));
     };
     return (
         <div className="grid grid-cols-4 gap-4 m-4">
@@ -166,9 +331,8 @@ const WindowAppearance: React.FC = () => {
                     type="color"
                     value={backgroundcolor}
                     onChange={e => {
-                        windowappearance_param.backgroundcolor = e.target.value;
-                        config_window.emit("on-change-windowappearance", windowappearance_param);
-                        setBackgroundColor(e.target.value)
+                        setBackgroundColor(e.target.value);
+                        set_windowappearance({ ...windowappearance_param, backgroundcolor: e.target.value });
                     }}
                 />
                 <span>{backgroundcolor}</span>
@@ -181,9 +345,8 @@ const WindowAppearance: React.FC = () => {
                     className="toggle toggle-primary"
                     checked={transparenttoggle}
                     onChange={e => {
-                        windowappearance_param.transparantetoggle = e.target.checked;
-                        config_window.emit("on-change-windowappearance", windowappearance_param);
-                        setTransparentToggle(e.target.checked)
+                        setTransparentToggle(e.target.checked);
+                        set_windowappearance({ ...windowappearance_param, transparantetoggle: e.target.checked });
                     }}
                 />
                 <input
@@ -195,19 +358,199 @@ const WindowAppearance: React.FC = () => {
                     value={backgroundopacity}
                     disabled={!transparenttoggle}
                     onChange={e => {
-                        windowappearance_param.backgroundopacity = parseInt(e.target.value);
-                        config_window.emit("on-change-windowappearance", windowappearance_param);
-                        setBackgroundOpacity(parseInt(e.target.value))
+                        setBackgroundOpacity(parseInt(e.target.value));
+                        set_windowappearance({ ...windowappearance_param, backgroundopacity: parseInt(e.target.value) });
                     }}
                 />
                 <span>{backgroundopacity}</span>
             </div>
+            {/************ Layout ************/}
+            <div className="col-span-1">Layout</div>
+            <div className="col-span-3 flex flex-row gap-2">
+                <select
+                    className="select select-sm select-bordered w-full"
+                    value={layout}
+                    onChange={e => {
+                        setLayout(e.target.value as Layout);
+                        set_windowappearance({ ...windowappearance_param, layout: e.target.value as Layout });
+                    }}
+                >
+                    <option value="horizontal">Horizontal</option>
+                    <option value="newest-on-top">Vertical, newest on top</option>
+                    <option value="newest-on-bottom">Vertical, newest at the bottom</option>
+                </select>
+            </div>
+            {/************ Theme ************/}
+            <div className="col-span-1">Theme</div>
+            <div className="col-span-3 flex flex-row gap-2">
+                <select
+                    className="select select-sm select-bordered w-full"
+                    value={theme}
+                    onChange={e => {
+                        setTheme(e.target.value);
+                        set_windowappearance({ ...windowappearance_param, theme: e.target.value });
+                    }}
+                >
+                    {!themes.includes(theme) && <option value={theme}>{theme} (missing)</option>}
+                    {themes.map(name => <option key={name} value={name}>{name}</option>)}
+                </select>
+            </div>
+            <ConfigError error={error} />
+        </div>
+    )
+}
+const BackendError: React.FC = () => {
+    const [error, setError] = useState<string | null>(null);
+    useEffect(() => {
+        let unlisten: UnlistenFn;
+        async function f() {
+            unlisten = await listen('backend-error', (event: Event<string>) => {
+                setError(event.payload);
+            });
+        }
+        f();
+        return () => {
+            if (unlisten) {
+                unlisten();
+            }
+        }
+    }, []);
+    if (error === null) {
+        return null;
+    }
+    return (
+        <div role="alert" className="alert alert-error my-2">
+            <span>Keyboard capture failed, retrying: {error}</span>
+            <button className="btn btn-sm" onClick={() => setError(null)}>Dismiss</button>
+        </div>
+    )
+}
+const anchors: Array<[Anchor, string]> = [
+    ["free", "Free (draggable)"],
+    ["top-left", "Top left"],
+    ["top", "Top"],
+    ["top-right", "Top right"],
+    ["left", "Left"],
+    ["center", "Center"],
+    ["right", "Right"],
+    ["bottom-left", "Bottom left"],
+    ["bottom", "Bottom"],
+    ["bottom-right", "Bottom right"],
+];
+
+const Position: React.FC = () => {
+    const [anchor, setAnchor] = useState<Anchor>("free");
+    const [margin, setMargin] = useState(16);
+    const [monitor, setMonitor] = useState<string | null>(null);
+    const [follow, setFollow] = useState<Follow>("off");
+    const [monitors, setMonitors] = useState<Array<MonitorInfo>>([]);
+    const position_param: PositionParameter = {
+        anchor: anchor,
+        margin: margin,
+        monitor: monitor,
+        follow: follow,
+    };
+    const [error, setError] = useState<string | null>(null);
+    const show = (applied: PositionParameter) => {
+        setAnchor(applied.anchor);
+        setMargin(applied.margin);
+        setMonitor(applied.monitor);
+        setFollow(applied.follow);
+    };
+    useConfig(config => show(config.position));
+    useEffect(() => {
+        invoke<Array<MonitorInfo>>("list_monitors").then(setMonitors);
+    }, []);
+    const set_position = (param: PositionParameter) => {
+        set_config({ position: param })
+            .then(config => {
+                setError(null);
+                show(config.position);
+            })
+            .catch(e => setError(String(e)));
+    };
+    return (
+        <div className="grid grid-cols-4 gap-4 m-4">
+            {/************ Anchor ************/}
+            <div className="col-span-1">Anchor</div>
+            <div className="col-span-3 flex flex-row gap-2">
+                <select
+                    className="select select-sm select-bordered w-full"
+                    value={anchor}
+                    onChange={e => {
+                        setAnchor(e.target.value as Anchor);
+                        set_position({ ...position_param, anchor: e.target.value as Anchor });
+                    }}
+                >
+                    {anchors.map(([value, label]) => <option key={value} value={value}>{label}</option>)}
+                </select>
+            </div>
+            {/************ Margin ************/}
+            <div className="col-span-1">Margin</div>
+            <div className="col-span-3 flex flex-row gap-2">
+                <input
+                    type="range"
+                    min={0}
+                    max={200}
+                    step={4}
+                    value={margin}
+                    disabled={anchor === "free"}
+                    className="range range-sm range-primary"
+                    onChange={e => {
+                        setMargin(parseInt(e.target.value));
+                        set_position({ ...position_param, margin: parseInt(e.target.value) });
+                    }}
+                />
+                <span>{margin}px</span>
+            </div>
+            {/************ Monitor ************/}
+            <div className="col-span-1">Monitor</div>
+            <div className="col-span-3 flex flex-row gap-2">
+                <select
+                    className="select select-sm select-bordered w-full"
+                    value={monitor ?? ""}
+                    disabled={follow !== "off"}
+                    onChange={e => {
+                        const name = e.target.value === "" ? null : e.target.value;
+                        setMonitor(name);
+                        set_position({ ...position_param, monitor: name });
+                    }}
+                >
+                    <option value="">Current</option>
+                    {/* keep a remembered monitor selectable while it is unplugged */}
+                    {monitor !== null && !monitors.some(m => m.name === monitor)
+                        ? <option value={monitor}>{monitor} (disconnected)</option>
+                        : null}
+                    {monitors.filter(m => m.name !== null).map(m =>
+                        <option key={m.name} value={m.name!}>{m.name} ({m.width}x{m.height})</option>
+                    )}
+                </select>
+            </div>
+            {/************ Follow ************/}
+            <div className="col-span-1">Follow</div>
+            <div className="col-span-3 flex flex-row gap-2">
+                <select
+                    className="select select-sm select-bordered w-full"
+                    value={follow}
+                    onChange={e => {
+                        setFollow(e.target.value as Follow);
+                        set_position({ ...position_param, follow: e.target.value as Follow });
+                    }}
+                >
+                    <option value="off">Off</option>
+                    <option value="pointer">Mouse pointer</option>
+                    <option value="focus">Focused window</option>
+                </select>
+            </div>
+            <ConfigError error={error} />
         </div>
     )
 }
+
 const App: React.FC = () => {
     return (
         <div className="flex flex-col px-2">
+            <BackendError />
             <div tabIndex={0} className="collapse collapse-open bg-base-200 border my-2">
                 <div className="collapse-title text-xl font-medium">Behavior</div>
                 <div className="collapse-content">
@@ -226,6 +569,12 @@ const App: React.FC = () => {
                     <WindowAppearance />
                 </div>
             </div>
+            <div tabIndex={3} className="collapse collapse-open bg-base-200 border my-2">
+                <div className="collapse-title text-xl font-medium">Position</div>
+                <div className="collapse-content">
+                    <Position />
+                </div>
+            </div>
         </div>
     )
 }
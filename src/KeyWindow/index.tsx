@@ -1,12 +1,11 @@
 import { listen, Event, UnlistenFn } from '@tauri-apps/api/event';
-import { WebviewWindow, PhysicalSize } from '@tauri-apps/api/window';
-import { useEffect, useState } from 'react';
-import { TypographyParameter, WindowAppearanceParameter } from '../parameter';
+import { convertFileSrc, invoke } from '@tauri-apps/api/tauri';
+import { CSSProperties, useEffect, useState } from 'react';
+import { Config, Key, Layout, Theme, TypographyParameter, WindowAppearanceParameter } from '../parameter';
 
 // interface Keystroke {
 //     symbols: Array<string>,
 // }
-const key_window: WebviewWindow = new WebviewWindow("KeyWindow");
 // TypographyParameter
 const initFontSize = (): number => {
     return 12;
@@ -27,8 +26,49 @@ const initBackgroundOpacity = (): number => {
 const initTransparentToggle = (): boolean => {
     return true;
 }
+const initLayout = (): Layout => {
+    return "horizontal";
+}
+// Theme: until it is known keys are drawn like the flat theme, all alike
+const initTheme = (): Theme => {
+    return {
+        keycap: "flat",
+        textcolor: null,
+        backgroundcolor: null,
+        classes: {},
+        bordercolor: "#808080",
+        shadowcolor: "#00000080",
+        radius: 4,
+        padding: 2,
+        shadow: 0,
+    };
+}
+// The padding and border here are what the backend sizes the window for.
+const keycapStyle = (theme: Theme, key: Key): CSSProperties => {
+    const style: CSSProperties = {
+        padding: theme.padding,
+        borderRadius: theme.radius,
+    };
+    const face = theme.classes[key.class]?.backgroundcolor || theme.backgroundcolor;
+    if (face) {
+        style.backgroundColor = face;
+    }
+    switch (theme.keycap) {
+        case "elevated":
+            style.boxShadow = `0 ${theme.shadow}px ${theme.shadow * 2}px ${theme.shadowcolor}`;
+            break;
+        case "mechanical":
+            style.border = `1px solid ${theme.bordercolor}`;
+            style.borderBottomWidth = 1 + theme.shadow;
+            break;
+        case "outlined":
+            style.border = `1px solid ${theme.bordercolor}`;
+            break;
+    }
+    return style;
+}
 // Keystrokes
-const initKeystrokes = (): Array<Array<string>> => {
+const initKeystrokes = (): Array<Array<Key>> => {
     return [];
 }
 
@@ -42,10 +82,44 @@ const App: React.FC = () => {
     const [backgroundcolor, setBackgroundColor] = useState<string>(initBackgroundColor);
     const [transparenttoggle, setTransparentToggle] = useState<boolean>(initTransparentToggle);
     const [backgroundopacity, setBackgroundOpacity] = useState<number>(initBackgroundOpacity);
+    const [layout, setLayout] = useState<Layout>(initLayout);
+    const [theme, setTheme] = useState<Theme>(initTheme);
+
+    // Presence: faded out while idle, the whole window can be dragged while moving
+    const [hidden, setHidden] = useState<boolean>(false);
+    const [moving, setMoving] = useState<boolean>(false);
 
     // Keystrokes
-    const [keystrokes, setKeystrokes] = useState<Array<Array<string>>>(initKeystrokes);
+    const [keystrokes, setKeystrokes] = useState<Array<Array<Key>>>(initKeystrokes);
 
+    // Config: start from the settings already in effect
+    useEffect(() => {
+        invoke<Config>('get_config').then(config => {
+            setFontSize(config.typography.fontsize);
+            setFontFamily(config.typography.fontfamily);
+            setTextColor(config.typography.textcolor);
+            setBackgroundColor(config.windowappearance.backgroundcolor);
+            setBackgroundOpacity(config.windowappearance.backgroundopacity);
+            setTransparentToggle(config.windowappearance.transparantetoggle);
+            setLayout(config.windowappearance.layout);
+        });
+        invoke<Theme>('get_theme').then(setTheme);
+    }, []);
+    // Theme: useEffect
+    useEffect(() => {
+        let unlisten: UnlistenFn;
+        async function f() {
+            unlisten = await listen('on-change-theme', (event: Event<Theme>) => {
+                setTheme(event.payload);
+            });
+        }
+        f();
+        return () => {
+            if (unlisten) {
+                unlisten();
+            }
+        }
+    }, []);
     // TypegraphyParameter: useEffect
     useEffect(() => {
         let unlisten: UnlistenFn;
@@ -72,6 +146,22 @@ const App: React.FC = () => {
                 setBackgroundColor(event.payload.backgroundcolor);
                 setBackgroundOpacity(event.payload.backgroundopacity);
                 setTransparentToggle(event.payload.transparantetoggle);
+                setLayout(event.payload.layout);
+            });
+        }
+        f();
+        return () => {
+            if (unlisten) {
+                unlisten();
+            }
+        }
+    }, []);
+    useEffect(() => {
+        let unlisten: UnlistenFn;
+        async function f() {
+            unlisten = await listen('on-change-presence', (event: Event<{ hidden: boolean, moving: boolean }>) => {
+                setHidden(event.payload.hidden);
+                setMoving(event.payload.moving);
             });
         }
         f();
@@ -84,10 +174,9 @@ const App: React.FC = () => {
     useEffect(() => {
         let unlisten: UnlistenFn;
         async function f() {
-            unlisten = await listen('keyevent', (event: Event<Array<Array<string>>>) => {
+            unlisten = await listen('keyevent', (event: Event<Array<Array<Key>>>) => {
+                // the window is sized to fit by the backend
                 setKeystrokes(event.payload);
-                const length = event.payload.length;
-                key_window.setSize(new PhysicalSize(150 + length * 32, 100));
             });
         }
         f();
@@ -99,7 +188,8 @@ const App: React.FC = () => {
     }, []);
     return (
         <div
-            className="flex flex-col justify-center p-1 gap-1"
+            {...(moving ? { "data-tauri-drag-region": true } : {})}
+            className={`flex flex-col justify-center p-1 gap-1 transition-opacity duration-300 ${hidden ? "opacity-0" : "opacity-100"} ${moving ? "outline-dashed outline-2 outline-primary cursor-move" : ""}`}
             style={{
                 backgroundColor: `color-mix(in srgb, ${backgroundcolor} ${transparenttoggle ? backgroundopacity : 100}%, transparent)`,
                 borderRadius: 5,
@@ -111,30 +201,38 @@ const App: React.FC = () => {
             >
             </div>
             <div
-                className="w-fit min-w-20 min-h-8 flex justify-start"
+                className={`w-fit min-w-20 min-h-8 flex justify-start ${layout === "horizontal" ? "flex-row" : "flex-col"}`}
             >
                 {
-                    keystrokes.map(keysyms => {
+                    keystrokes.map(keys => {
                         return (
                             <div
-                                className="flex justify-start p-0.5"
+                                className="flex justify-start gap-0.5 p-0.5"
                                 style={{
                                     backgroundColor: `color-mix(in srgb, ${backgroundcolor} ${transparenttoggle ? backgroundopacity : 100}%, transparent)`,
                                 }}
                             >
                                 {
-                                    keysyms.map(keysym => {
+                                    keys.map(key => {
                                         return (
                                             <div
-                                                className="w-fit min-w-8 p-0.5 flex justify-center"
+                                                className="w-fit min-w-8 flex justify-center items-center gap-1"
                                                 style={{
                                                     backgroundColor: `color-mix(in srgb, ${backgroundcolor} ${transparenttoggle ? backgroundopacity : 100}%, transparent)`,
-                                                    color: `${textcolor}`,
+                                                    ...keycapStyle(theme, key),
+                                                    color: `${theme.classes[key.class]?.textcolor || theme.textcolor || textcolor}`,
                                                     fontSize: `${fontsize}px`,
                                                     fontFamily: `${fontfamily}`,
                                                 }}
                                             >
-                                                {keysym}
+                                                {key.icon &&
+                                                    <img
+                                                        src={convertFileSrc(key.icon)}
+                                                        className="object-contain"
+                                                        style={{ width: `${fontsize * 1.5}px`, height: `${fontsize * 1.5}px` }}
+                                                    />
+                                                }
+                                                {key.label}
                                             </div>
                                         );
                                     })
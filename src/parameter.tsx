@@ -2,8 +2,13 @@ export interface BehaviorParameter {
     timeout: number;
     mousevisible: boolean;
     modvisible: boolean;
+    modifierside: boolean;
+    clickthrough: boolean;
+    display: DisplayStyle;
 }
 
+export type DisplayStyle = "always" | "while-inputting";
+
 export interface TypographyParameter {
     fontsize: number;
     fontfamily: string;
@@ -14,4 +19,81 @@ export interface WindowAppearanceParameter {
     backgroundcolor: string;
     transparantetoggle: boolean;
     backgroundopacity: number;
+    layout: Layout;
+    theme: string;
+}
+
+export type KeycapStyle = "flat" | "elevated" | "mechanical" | "outlined";
+
+export type KeyClass = "modifier" | "navigation" | "function" | "editing" | "printable" | "mouse";
+
+// A key as sent with the keyevent event, icon is an absolute file path.
+export interface Key {
+    label: string;
+    class: KeyClass;
+    icon?: string;
+}
+
+// Colors of a class of keys, null for those of all keys.
+export interface ClassColors {
+    textcolor: string | null;
+    backgroundcolor: string | null;
+}
+
+// A keycap theme, colors left null fall back to the typography and window
+// appearance.
+export interface Theme {
+    keycap: KeycapStyle;
+    textcolor: string | null;
+    backgroundcolor: string | null;
+    classes: Partial<Record<KeyClass, ClassColors>>;
+    bordercolor: string;
+    shadowcolor: string;
+    radius: number;
+    padding: number;
+    shadow: number;
+}
+
+export type Layout = "horizontal" | "newest-on-top" | "newest-on-bottom";
+
+export interface FontFamily {
+    name: string;
+    monospace: boolean;
+    generic: boolean;
+}
+
+export type Anchor = "free"
+    | "top-left" | "top" | "top-right"
+    | "left" | "center" | "right"
+    | "bottom-left" | "bottom" | "bottom-right";
+
+export type Follow = "off" | "pointer" | "focus";
+
+export interface PositionParameter {
+    anchor: Anchor;
+    margin: number;
+    monitor: string | null;
+    follow: Follow;
+}
+
+export interface MonitorInfo {
+    name: string | null;
+    width: number;
+    height: number;
+    scale_factor: number;
+}
+
+export interface Config {
+    behavior: BehaviorParameter;
+    typography: TypographyParameter;
+    windowappearance: WindowAppearanceParameter;
+    position: PositionParameter;
+}
+
+// Sections left out are kept as they are.
+export interface ConfigPatch {
+    behavior?: BehaviorParameter;
+    typography?: TypographyParameter;
+    windowappearance?: WindowAppearanceParameter;
+    position?: PositionParameter;
 }